@@ -0,0 +1,47 @@
+//! AMD GPU utilization, VRAM and clocks from the amdgpu sysfs interface. Temperatures already come
+//! through hwmon.
+use crate::hwmon::device_path;
+use std::fs;
+use std::path::PathBuf;
+
+/// `device` is the PCI device below `/sys/devices`, the same path the GPU's hwmon chip resolves to.
+#[derive(Clone, Debug)]
+pub struct AmdGpu { pub device: String, pub dir: PathBuf }
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AmdReading { pub busy_pct: Option<f64>, pub vram_pct: Option<f64>, pub sclk_mhz: Option<f64>, pub mclk_mhz: Option<f64> }
+
+pub fn discover() -> Vec<AmdGpu> {
+    let mut gpus = vec![];
+    if let Ok(entries) = fs::read_dir("/sys/class/drm") {
+        for e in entries.flatten() {
+            let name = e.file_name().to_string_lossy().into_owned();
+            // cardN only; cardN-DP-1 and friends are connectors
+            if !name.starts_with("card") || name[4..].parse::<u32>().is_err() { continue; }
+            let Ok(dir) = fs::canonicalize(e.path().join("device")) else { continue };
+            let driver = fs::canonicalize(dir.join("driver")).ok().and_then(|d| d.file_name().map(|n| n.to_string_lossy().into_owned()));
+            if driver.as_deref() != Some("amdgpu") { continue; }
+            gpus.push(AmdGpu { device: device_path(&dir), dir });
+        }
+    }
+    gpus.sort_by(|a, b| a.device.cmp(&b.device));
+    gpus.dedup_by(|a, b| a.device == b.device);
+    gpus
+}
+
+pub fn read(gpu: &AmdGpu) -> AmdReading {
+    let num = |f: &str| fs::read_to_string(gpu.dir.join(f)).ok().and_then(|s| s.trim().parse::<f64>().ok());
+    let vram_pct = match (num("mem_info_vram_used"), num("mem_info_vram_total")) {
+        (Some(used), Some(total)) if total > 0.0 => Some(used / total * 100.0),
+        _ => None,
+    };
+    AmdReading { busy_pct: num("gpu_busy_percent"), vram_pct, sclk_mhz: current_dpm_mhz(gpu, "pp_dpm_sclk"), mclk_mhz: current_dpm_mhz(gpu, "pp_dpm_mclk") }
+}
+
+/// The active DPM level is the line marked `*`, e.g. `1: 1000Mhz *`.
+fn current_dpm_mhz(gpu: &AmdGpu, file: &str) -> Option<f64> {
+    let s = fs::read_to_string(gpu.dir.join(file)).ok()?;
+    let line = s.lines().find(|l| l.trim_end().ends_with('*'))?;
+    let mhz = line.split_whitespace().nth(1)?;
+    mhz.to_lowercase().trim_end_matches("mhz").parse().ok()
+}
@@ -0,0 +1,103 @@
+//! Per-sensor calibration: offset/scale corrections keyed by stable sensor id, kept in
+//! `~/.config/sia/calibration.toml`:
+//!
+//! ```toml
+//! ["coretemp@platform/coretemp.0/temp1"]
+//! offset = -2.5
+//! scale = 1.0
+//! ```
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// `corrected = raw * scale + offset`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Calibration { pub offset: f64, pub scale: f64 }
+
+impl Default for Calibration { fn default() -> Self { Self { offset: 0.0, scale: 1.0 } } }
+
+impl Calibration {
+    pub fn apply(&self, raw: f64) -> f64 { raw * self.scale + self.offset }
+    pub fn is_identity(&self) -> bool { *self == Self::default() }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CalibrationError {
+    #[error("no config directory (HOME unset)")]
+    NoPath,
+    #[error("writing {0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("encoding calibration: {0}")]
+    Toml(#[from] toml::ser::Error),
+}
+
+#[derive(Debug, Default)]
+pub struct Calibrations { by_id: BTreeMap<String, Calibration>, path: Option<PathBuf> }
+
+impl Calibrations {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let by_id = path.as_ref().filter(|p| p.exists()).and_then(|p| {
+            let parsed = fs::read_to_string(p).map_err(|e| e.to_string()).and_then(|s| toml::from_str(&s).map_err(|e| e.to_string()));
+            parsed.map_err(|e| eprintln!("sia: ignoring {}: {}", p.display(), e)).ok()
+        }).unwrap_or_default();
+        Self { by_id, path }
+    }
+
+    pub fn get(&self, id: &str) -> Calibration { self.by_id.get(id).copied().unwrap_or_default() }
+
+    pub fn set(&mut self, id: &str, c: Calibration) {
+        if c.is_identity() { self.by_id.remove(id); } else { self.by_id.insert(id.to_string(), c); }
+    }
+
+    pub fn apply(&self, id: &str, raw: f64) -> f64 { self.by_id.get(id).map(|c| c.apply(raw)).unwrap_or(raw) }
+
+    pub fn save(&self) -> Result<(), CalibrationError> {
+        let path = self.path.as_ref().ok_or(CalibrationError::NoPath)?;
+        let io = |e| CalibrationError::Io(path.display().to_string(), e);
+        if let Some(dir) = path.parent() { fs::create_dir_all(dir).map_err(io)?; }
+        fs::write(path, toml::to_string(&self.by_id)?).map_err(io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_offset_and_scale() {
+        let c = Calibration { offset: -2.5, scale: 2.0 };
+        assert_eq!(c.apply(40.0), 77.5);
+        assert_eq!(Calibration::default().apply(40.0), 40.0);
+        let mut cal = Calibrations::default();
+        cal.set("a/temp1", c);
+        assert_eq!(cal.apply("a/temp1", 10.0), 17.5);
+        assert_eq!(cal.apply("b/temp1", 10.0), 10.0);
+        // setting the identity drops the entry
+        cal.set("a/temp1", Calibration::default());
+        assert_eq!(cal.get("a/temp1"), Calibration::default());
+        assert!(cal.by_id.is_empty());
+    }
+
+    #[test]
+    fn save_and_load_by_id() {
+        let path = std::env::temp_dir().join(format!("sia-calib-{}", std::process::id())).join("calibration.toml");
+        let mut cal = Calibrations::load(Some(path.clone()));
+        assert!(cal.by_id.is_empty());
+        cal.set("coretemp@platform/coretemp.0/temp1", Calibration { offset: -2.5, scale: 1.0 });
+        cal.set("nvme@pci0000:00/0000:00:1d.0/0000:04:00.0/temp1", Calibration { offset: 0.0, scale: 1.1 });
+        cal.save().unwrap();
+        let back = Calibrations::load(Some(path.clone()));
+        assert_eq!(back.by_id, cal.by_id);
+        assert_eq!(back.apply("coretemp@platform/coretemp.0/temp1", 50.0), 47.5);
+        // a missing field keeps its default
+        fs::write(&path, "[\"x/temp2\"]\noffset = 3.0\n").unwrap();
+        assert_eq!(Calibrations::load(Some(path.clone())).get("x/temp2"), Calibration { offset: 3.0, scale: 1.0 });
+        // an unreadable file is ignored rather than fatal
+        fs::write(&path, "[\"x/temp2\"]\nofset = 3.0\n").unwrap();
+        assert!(Calibrations::load(Some(path.clone())).by_id.is_empty());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert!(matches!(Calibrations::default().save(), Err(CalibrationError::NoPath)));
+    }
+}
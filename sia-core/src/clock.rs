@@ -0,0 +1,19 @@
+//! Clocks that `std` does not expose. `Instant` is CLOCK_MONOTONIC on Linux, which stops while the machine
+//! is suspended; CLOCK_BOOTTIME keeps counting, so the difference between the two is time spent suspended.
+use std::time::Duration;
+
+/// Time since boot, including time spent suspended.
+pub fn boottime() -> Duration {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // SAFETY: clock_gettime only writes the timespec we pass; CLOCK_BOOTTIME exists since Linux 2.6.39
+    if unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut ts) } != 0 { return Duration::ZERO; }
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// CPU time used by this process so far, all threads together.
+pub fn process_cpu() -> Duration {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // SAFETY: as above
+    if unsafe { libc::clock_gettime(libc::CLOCK_PROCESS_CPUTIME_ID, &mut ts) } != 0 { return Duration::ZERO; }
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
@@ -0,0 +1,250 @@
+//! The sampling API: a [`Collector`] owns every backend, describes what it measures as [`Channel`]s and
+//! turns each [`Collector::sample`] call into one [`Snapshot`].
+use crate::calibration::Calibrations;
+use crate::cpufreq::{self, FreqSensor};
+use crate::hwmon::{self, TempSensor};
+use crate::rules::Rules;
+use crate::sensors_conf::SensorsConf;
+use crate::taxonomy::{build_groups, palette, Rgb, SensorGroup, SensorItem};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use sysinfo::{CpuExt, System, SystemExt};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Kind { Utilization, Temperature, Frequency }
+
+impl Kind {
+    /// Unit of every value of this kind in a [`Snapshot`].
+    pub fn unit(&self) -> &'static str { match self { Kind::Utilization => "%", Kind::Temperature => "°C", Kind::Frequency => "MHz" } }
+}
+
+/// One measured quantity. `id` is stable across reboots; `group` is the key of the [`SensorGroup`] it belongs
+/// to (`"util"` for machine-wide utilization); `chip` is the driver or backend that reports it.
+#[derive(Clone, Debug)]
+pub struct Channel { pub id: String, pub name: String, pub kind: Kind, pub group: String, pub chip: String, pub device: String, pub color: Rgb }
+
+/// One reading of every channel, indexed like [`Collector::channels`]. `None` is a failed read.
+/// `raw` is the reading as the sensor reported it, before sensors.conf `compute` and calibration.
+/// `mono`, `boot` and `wall` are taken together when the sample starts: `mono` to measure intervals,
+/// `boot` (CLOCK_BOOTTIME, see [`crate::clock`]) to tell suspends apart from elapsed time, `wall` to line
+/// samples up with logs.
+#[derive(Clone, Debug)]
+pub struct Snapshot { pub mono: Instant, pub boot: Duration, pub wall: SystemTime, pub values: Vec<Option<f64>>, pub raw: Vec<Option<f64>> }
+
+/// Where the rule table and calibration live (`None` means built-in rules only / no calibration file), and
+/// which backends to probe. CPU and RAM utilization are always sampled.
+#[derive(Clone, Debug)]
+pub struct CollectorConfig {
+    pub rules_file: Option<PathBuf>,
+    pub calibration_file: Option<PathBuf>,
+    pub hwmon: bool,
+    pub cpufreq: bool,
+    /// Ignored unless built with the `nvidia` feature.
+    pub nvidia: bool,
+    /// Ignored unless built with the `amdgpu` feature.
+    pub amdgpu: bool,
+}
+
+impl Default for CollectorConfig {
+    fn default() -> Self {
+        let dir = crate::config_dir();
+        Self { rules_file: dir.as_ref().map(|d| d.join("rules.toml")), calibration_file: dir.map(|d| d.join("calibration.toml")), hwmon: true, cpufreq: true, nvidia: true, amdgpu: true }
+    }
+}
+
+impl CollectorConfig {
+    /// Backend names accepted by [`CollectorConfig::enable`].
+    pub const BACKENDS: [&'static str; 4] = ["hwmon", "cpufreq", "nvidia", "amdgpu"];
+
+    /// Turns the backend called `name` on or off.
+    pub fn enable(&mut self, name: &str, on: bool) -> Result<(), String> {
+        let flag = match name { "hwmon" => &mut self.hwmon, "cpufreq" => &mut self.cpufreq, "nvidia" => &mut self.nvidia, "amdgpu" => &mut self.amdgpu,
+            _ => return Err(format!("{}: unknown collector (expected one of {})", name, Self::BACKENDS.join(", "))) };
+        *flag = on;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "nvidia")]
+struct NvChannels { temp: usize, util: usize, vram: usize, clocks: [usize; 4] }
+
+#[cfg(feature = "amdgpu")]
+struct AmdChannels { util: usize, vram: usize, sclk: usize, mclk: usize }
+
+pub struct Collector {
+    sys: System,
+    temps: Vec<TempSensor>,
+    freqs: Vec<FreqSensor>,
+    channels: Vec<Channel>,
+    groups: Vec<SensorGroup>,
+    calib: Calibrations,
+    cpu_util: usize,
+    ram_util: usize,
+    freq_base: usize,
+    #[cfg(feature = "nvidia")]
+    nv: Option<(crate::nvgpu::NvState, NvChannels)>,
+    #[cfg(feature = "amdgpu")]
+    amd: Vec<(crate::amdgpu::AmdGpu, AmdChannels)>,
+}
+
+impl Default for Collector { fn default() -> Self { Self::new(&CollectorConfig::default()) } }
+
+impl Collector {
+    /// Discovers every sensor and backend. Temperature channels come first, in the order of the hwmon
+    /// discovery, so a [`SensorItem::idx`] is both a sensor and a channel index.
+    pub fn new(config: &CollectorConfig) -> Self {
+        let mut sys = System::new_all();
+        sys.refresh_all();
+        let rules = Rules::load(config.rules_file.as_deref());
+        let temps = if config.hwmon { hwmon::discover(&SensorsConf::load()) } else { vec![] };
+        let freqs = if config.cpufreq { cpufreq::discover() } else { vec![] };
+        let mut groups = build_groups(&temps, &rules);
+
+        let mut channels: Vec<Channel> = vec![];
+        for g in &groups {
+            for it in &g.items {
+                let s = &temps[it.idx];
+                // items are sorted within groups; channels stay in discovery order
+                let ch = Channel { id: it.id.clone(), name: it.name.clone(), kind: Kind::Temperature, group: g.key.clone(), chip: s.raw_name.clone(), device: s.device.clone(), color: it.color };
+                if channels.len() <= it.idx { channels.resize(it.idx + 1, ch.clone()); }
+                channels[it.idx] = ch;
+            }
+        }
+        let util = |id: &str, name: &str, color: Rgb, chip: &str, device: &str| Channel { id: id.into(), name: name.into(), kind: Kind::Utilization, group: "util".into(), chip: chip.into(), device: device.into(), color };
+        let cpu_util = channels.len();
+        channels.push(util("cpu/util", "CPU", rules.group_color("cpu"), "sysinfo", ""));
+        let ram_util = channels.len();
+        channels.push(util("ram/util", "RAM", rules.group_color("ramspd"), "sysinfo", ""));
+
+        // CPU core clocks, in the CPU group of their socket
+        let pal = palette();
+        let freq_base = channels.len();
+        if !freqs.is_empty() && !groups.iter().any(|g| g.kind == "cpu") { groups.push(SensorGroup::empty("cpu".into(), "cpu", &rules)); }
+        for (i, fs) in freqs.iter().enumerate() {
+            let idx = channels.len();
+            let color = pal[i % pal.len()];
+            let g = groups.iter_mut().filter(|g| g.kind == "cpu").find(|g| g.socket.map(|n| n == fs.package).unwrap_or(true));
+            let key = g.as_ref().map(|g| g.key.clone()).unwrap_or_else(|| "cpu".into());
+            let name = format!("CPU Core {}", fs.core);
+            if let Some(g) = g { g.freqs.push(SensorItem { id: fs.id.clone(), name: name.clone(), idx, visible: true, color }); }
+            channels.push(Channel { id: fs.id.clone(), name, kind: Kind::Frequency, group: key, chip: "cpufreq".into(), device: format!("system/cpu/cpu{}", fs.core), color });
+        }
+
+        #[cfg(feature = "nvidia")]
+        let nv = config.nvidia.then(crate::nvgpu::NvState::try_new).flatten().map(|state| {
+            let bus = crate::nvgpu::bus_id(&state);
+            let device = bus.clone().unwrap_or_else(|| format!("nvml/index{}", state.device_index));
+            // attach to the hwmon group of the same PCI device, if any
+            let key = match groups.iter().find(|g| g.kind == "gpu" && bus.as_ref().map(|b| g.key.ends_with(&format!("/{}", b))).unwrap_or(false)) {
+                Some(g) => g.key.clone(),
+                None => { let key = format!("gpu@nvml/{}", device); groups.push(SensorGroup::empty(key.clone(), "nvidia", &rules)); key }
+            };
+            let g = groups.iter_mut().find(|g| g.key == key).expect("group just ensured");
+            let mut add = |channels: &mut Vec<Channel>, id: String, name: &str, kind: Kind, visible: bool, color: Rgb| {
+                let idx = channels.len();
+                let item = SensorItem { id: id.clone(), name: name.into(), idx, visible, color };
+                match kind { Kind::Temperature => g.items.push(item), Kind::Frequency => g.freqs.push(item), Kind::Utilization => {} }
+                let group = if kind == Kind::Utilization { "util".to_string() } else { key.clone() };
+                channels.push(Channel { id, name: name.into(), kind, group, chip: "nvml".into(), device: device.clone(), color });
+                idx
+            };
+            let base = format!("nvml@{}", device);
+            let temp = add(&mut channels, format!("{}/temp", base), "GPU (Core)", Kind::Temperature, true, Rgb::WHITE);
+            let util = add(&mut channels, format!("{}/util", base), "GPU", Kind::Utilization, true, rules.group_color("gpu"));
+            let vram = add(&mut channels, format!("{}/vram", base), "VRAM", Kind::Utilization, true, rules.group_color("nvme"));
+            let clocks = [("graphics", "GPU Graphics", true, 1), ("sm", "GPU SM", true, 2), ("mem", "GPU Memory", true, 3), ("video", "GPU Video", false, 4)]
+                .map(|(k, name, vis, c)| add(&mut channels, format!("{}/clock/{}", base, k), name, Kind::Frequency, vis, pal[c]));
+            (state, NvChannels { temp, util, vram, clocks })
+        });
+
+        #[cfg(feature = "amdgpu")]
+        let amd = if config.amdgpu { crate::amdgpu::discover() } else { vec![] }.into_iter().map(|gpu| {
+            let key = match groups.iter().find(|g| g.kind == "gpu" && g.key.ends_with(&format!("@{}", gpu.device))) {
+                Some(g) => g.key.clone(),
+                None => { let key = format!("gpu@{}", gpu.device); groups.push(SensorGroup::empty(key.clone(), "amdgpu", &rules)); key }
+            };
+            let g = groups.iter_mut().find(|g| g.key == key).expect("group just ensured");
+            let base = format!("amdgpu@{}", gpu.device);
+            let mut push = |id: &str, name: &str, kind: Kind, color: Rgb| {
+                let idx = channels.len();
+                if kind == Kind::Frequency { g.freqs.push(SensorItem { id: format!("{}/{}", base, id), name: name.into(), idx, visible: true, color }); }
+                let group = if kind == Kind::Utilization { "util".to_string() } else { key.clone() };
+                channels.push(Channel { id: format!("{}/{}", base, id), name: name.into(), kind, group, chip: "amdgpu".into(), device: gpu.device.clone(), color });
+                idx
+            };
+            let util = push("util", "GPU (amdgpu)", Kind::Utilization, rules.group_color("gpu"));
+            let vram = push("vram", "VRAM (amdgpu)", Kind::Utilization, rules.group_color("nvme"));
+            let sclk = push("clock/graphics", "GPU Graphics (amdgpu)", Kind::Frequency, pal[1]);
+            let mclk = push("clock/mem", "GPU Memory (amdgpu)", Kind::Frequency, pal[3]);
+            (gpu, AmdChannels { util, vram, sclk, mclk })
+        }).collect();
+
+        groups.sort_by_key(|g| g.rank);
+        Self {
+            sys, temps, freqs, channels, groups,
+            calib: Calibrations::load(config.calibration_file.clone()),
+            cpu_util, ram_util, freq_base,
+            #[cfg(feature = "nvidia")]
+            nv,
+            #[cfg(feature = "amdgpu")]
+            amd,
+        }
+    }
+
+    pub fn channels(&self) -> &[Channel] { &self.channels }
+
+    /// Temperature groups per physical device, in display order, with default visibility and colors.
+    pub fn groups(&self) -> &[SensorGroup] { &self.groups }
+
+    pub fn calibration(&self) -> &Calibrations { &self.calib }
+    pub fn calibration_mut(&mut self) -> &mut Calibrations { &mut self.calib }
+
+    /// Reads every channel once. Utilization is in %, temperatures in °C, frequencies in MHz.
+    pub fn sample(&mut self) -> Snapshot {
+        let (mono, boot, wall) = (Instant::now(), crate::clock::boottime(), SystemTime::now());
+        let mut values: Vec<Option<f64>> = vec![None; self.channels.len()];
+
+        self.sys.refresh_cpu();
+        self.sys.refresh_memory();
+        let avg_cpu: f32 = self.sys.cpus().iter().map(|c| c.cpu_usage()).sum::<f32>() / (self.sys.cpus().len().max(1) as f32);
+        values[self.cpu_util] = Some(avg_cpu as f64);
+        let total = self.sys.total_memory() as f64;
+        let used = self.sys.used_memory() as f64;
+        values[self.ram_util] = Some(if total>0.0 { (used/total)*100.0 } else { 0.0 });
+
+        for (i, ts) in self.temps.iter().enumerate() { values[i] = hwmon::read_temp_c(&ts.path); }
+        for (i, fs) in self.freqs.iter().enumerate() { values[self.freq_base + i] = cpufreq::read_freq_khz(&fs.path).map(|khz| khz / 1000.0); }
+
+        #[cfg(feature = "nvidia")]
+        if let Some((nv, ch)) = &self.nv {
+            if let Some((gpu_pct, vram_pct, temp_c)) = crate::nvgpu::first_gpu_metrics(nv) {
+                values[ch.util] = Some(gpu_pct);
+                values[ch.vram] = Some(vram_pct);
+                values[ch.temp] = Some(temp_c);
+            }
+            if let Some((g, sm, m, v)) = crate::nvgpu::gpu_clocks_mhz(nv) {
+                for (idx, mhz) in ch.clocks.iter().zip([g, sm, m, v]) { values[*idx] = Some(mhz); }
+            }
+        }
+
+        #[cfg(feature = "amdgpu")]
+        for (gpu, ch) in &self.amd {
+            let r = crate::amdgpu::read(gpu);
+            values[ch.util] = r.busy_pct;
+            values[ch.vram] = r.vram_pct;
+            values[ch.sclk] = r.sclk_mhz;
+            values[ch.mclk] = r.mclk_mhz;
+        }
+
+        // sensors.conf compute, then calibration, on temperatures
+        let raw = values.clone();
+        for (i, ch) in self.channels.iter().enumerate() {
+            if ch.kind != Kind::Temperature { continue; }
+            if let Some(v) = values[i].as_mut() {
+                if let Some(e) = self.temps.get(i).and_then(|t| t.compute.as_ref()) { *v = e.eval(*v); }
+                *v = self.calib.apply(&ch.id, *v);
+            }
+        }
+        Snapshot { mono, boot, wall, values, raw }
+    }
+}
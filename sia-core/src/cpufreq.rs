@@ -0,0 +1,31 @@
+//! Per-core CPU frequency discovery.
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// `package` is the physical package (socket) the core belongs to.
+#[derive(Clone, Debug)]
+pub struct FreqSensor { pub id: String, pub core: usize, pub package: usize, pub path: PathBuf }
+
+pub fn discover() -> Vec<FreqSensor> {
+    let mut sensors = vec![];
+    if let Ok(entries) = fs::read_dir("/sys/devices/system/cpu") {
+        for e in entries.flatten() {
+            let p = e.path();
+            let name = p.file_name().and_then(|s| s.to_str()).unwrap_or("");
+            if !name.starts_with("cpu") { continue; }
+            let idx = match name.trim_start_matches("cpu").parse::<usize>() { Ok(v)=>v, Err(_)=>continue };
+            let cf = p.join("cpufreq");
+            let cand1 = cf.join("scaling_cur_freq");
+            let cand2 = cf.join("cpuinfo_cur_freq");
+            let path = if cand1.exists() { cand1 } else if cand2.exists() { cand2 } else { continue };
+            let package = fs::read_to_string(p.join("topology/physical_package_id")).ok().and_then(|s| s.trim().parse::<usize>().ok()).unwrap_or(0);
+            sensors.push(FreqSensor { id: format!("cpu{}/freq", idx), core: idx, package, path });
+        }
+    }
+    sensors.sort_by_key(|s| s.core);
+    sensors
+}
+
+/// cpufreq ABI: `scaling_cur_freq` / `cpuinfo_cur_freq` are kHz.
+pub fn read_freq_khz(path: &Path) -> Option<f64> { let mut s=String::new(); fs::File::open(path).ok()?.read_to_string(&mut s).ok()?; s.trim().parse::<f64>().ok() }
@@ -0,0 +1,91 @@
+//! hwmon temperature discovery.
+use crate::sensors_conf::{self, SensorsConf};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// A hwmon temperature channel. `id` is stable across reboots: it is built from the physical device the
+/// chip hangs off (PCI/platform address), the chip name and the channel number, never from `hwmonN`.
+/// `labelled` means `raw_label` came from sensors.conf and is shown as-is; `compute` is its sensors.conf correction.
+#[derive(Clone, Debug)]
+pub struct TempSensor { pub id: String, pub raw_name: String, pub raw_label: String, pub labelled: bool, pub compute: Option<sensors_conf::Expr>, pub device: String, pub model: Option<String>, pub serial: Option<String>, pub path: PathBuf }
+
+/// True when `dir` is a device on a real bus (pci, platform, i2c, ...) rather than a class device such as `nvme0`.
+fn is_bus_device(dir: &Path) -> bool {
+    fs::canonicalize(dir.join("subsystem")).map(|s| s.starts_with("/sys/bus")).unwrap_or(false)
+}
+
+/// The bus device (pci, platform, i2c, ...) a hwmon chip hangs off. Class devices like `nvme/nvme0` are walked
+/// up to their bus device because their numbering is not stable. `None` for chips without one (thermal zones).
+pub fn bus_device(base: &Path) -> Option<PathBuf> {
+    let mut cur = fs::canonicalize(base.join("device")).ok()?;
+    loop {
+        if is_bus_device(&cur) { return Some(cur); }
+        if !cur.pop() || !cur.starts_with("/sys/devices/") { return None; }
+    }
+}
+
+/// `/sys/devices/pci0000:00/...` -> `pci0000:00/...`, the form device paths take in ids and groups.
+pub fn device_path(p: &Path) -> String {
+    p.strip_prefix("/sys/devices").map(|p| p.to_string_lossy().into_owned()).unwrap_or_else(|_| p.to_string_lossy().into_owned())
+}
+
+/// Physical device of a hwmon chip, relative to `/sys/devices` (e.g. `pci0000:00/0000:00:1d.0/0000:04:00.0`).
+/// Chips without a bus device fall back to their virtual path.
+pub fn resolve_device(base: &Path) -> String {
+    let resolved = bus_device(base)
+        .or_else(|| fs::canonicalize(base.join("device")).ok())
+        .or_else(|| fs::canonicalize(base).ok().and_then(|p| p.parent().map(Path::to_path_buf)))
+        .unwrap_or_else(|| base.to_path_buf());
+    device_path(&resolved)
+}
+
+/// Model and serial of the device behind a hwmon chip, when it publishes them (NVMe controllers, SATA disks).
+fn device_ident(base: &Path) -> (Option<String>, Option<String>) {
+    let read = |p: PathBuf| fs::read_to_string(p).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let dev = base.join("device");
+    let mut dirs = vec![dev.clone()];
+    // newer kernels hang the nvme hwmon off the PCI function rather than the nvme controller
+    if let Ok(ctrls) = fs::read_dir(dev.join("nvme")) { dirs.extend(ctrls.flatten().map(|e| e.path())); }
+    for d in dirs { if let Some(model) = read(d.join("model")) { return (Some(model), read(d.join("serial"))); } }
+    (None, None)
+}
+
+fn stable_id(chip: &str, device: &str, channel: u32) -> String { format!("{}@{}/temp{}", chip, device, channel) }
+
+/// Every `tempN_input` under `/sys/class/hwmon`, with sensors.conf labels, ignores and computes applied.
+pub fn discover(conf: &SensorsConf) -> Vec<TempSensor> {
+    let mut sensors = vec![];
+    if let Ok(entries) = fs::read_dir("/sys/class/hwmon") {
+        for e in entries.flatten() {
+            let base = e.path();
+            let name = fs::read_to_string(base.join("name")).unwrap_or_default().trim().to_string();
+            let device = resolve_device(&base);
+            let (model, serial) = device_ident(&base);
+            let hwmon_nr = base.file_name().and_then(|s| s.to_str()).and_then(|s| s.trim_start_matches("hwmon").parse::<u32>().ok()).unwrap_or(0);
+            let chip = sensors_conf::chip_name(&name, bus_device(&base).as_deref(), hwmon_nr);
+            if let Ok(files) = fs::read_dir(&base) {
+                for f in files.flatten() {
+                    let p = f.path(); let fname = p.file_name().and_then(|s| s.to_str()).unwrap_or("");
+                    if fname.starts_with("temp") && fname.ends_with("_input") {
+                        let channel = match fname.trim_start_matches("temp").trim_end_matches("_input").parse::<u32>() { Ok(v)=>v, Err(_)=>continue };
+                        let mut label = name.clone();
+                        let label_path = base.join(fname.replace("_input","_label"));
+                        if let Ok(lbl) = fs::read_to_string(label_path) { let l=lbl.trim(); if !l.is_empty() { label = l.to_string(); } }
+                        let tuning = conf.tuning(&chip, channel);
+                        if tuning.ignore { continue; }
+                        let labelled = tuning.label.is_some();
+                        if let Some(l) = tuning.label { label = l; }
+                        sensors.push(TempSensor { id: stable_id(&name, &device, channel), raw_name: name.clone(), raw_label: label, labelled, compute: tuning.compute, device: device.clone(), model: model.clone(), serial: serial.clone(), path: p.clone() });
+                    }
+                }
+            }
+        }
+    }
+    // read_dir order and hwmonN numbering vary between boots; the stable id does not
+    sensors.sort_by(|a, b| a.id.cmp(&b.id));
+    sensors
+}
+
+/// hwmon ABI: `tempN_input` is always millidegrees Celsius, so there is no guessing from the magnitude.
+pub fn read_temp_c(path: &Path) -> Option<f64> { let mut s=String::new(); fs::File::open(path).ok()?.read_to_string(&mut s).ok()?; let v: f64 = s.trim().parse().ok()?; Some(v / 1000.0) }
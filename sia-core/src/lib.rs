@@ -0,0 +1,64 @@
+//! Sensor discovery, naming and sampling for sia.
+//!
+//! ```no_run
+//! let mut c = sia_core::Collector::default();
+//! let snap = c.sample();
+//! for (ch, v) in c.channels().iter().zip(&snap.values) {
+//!     if let Some(v) = v { println!("{} {:.1} {}", ch.id, v, ch.kind.unit()); }
+//! }
+//! ```
+use std::path::PathBuf;
+
+pub mod calibration;
+pub mod clock;
+pub mod collector;
+pub mod cpufreq;
+pub mod hwmon;
+pub mod metrics;
+pub mod mqtt;
+pub mod push;
+pub mod record;
+pub mod remote;
+pub mod replay;
+pub mod rules;
+pub mod sampler;
+pub mod sensors_conf;
+pub mod series;
+pub mod taxonomy;
+
+#[cfg(feature = "amdgpu")]
+pub mod amdgpu;
+#[cfg(feature = "nvidia")]
+pub mod nvgpu;
+
+pub use calibration::{Calibration, CalibrationError, Calibrations};
+pub use collector::{Channel, Collector, CollectorConfig, Kind, Snapshot};
+pub use metrics::Exporter;
+pub use mqtt::MqttConfig;
+pub use push::{PushConfig, Pusher, Sink, SinkThread};
+pub use record::{Recorder, Recording};
+pub use remote::{Agent, Endpoint, Remote};
+pub use replay::Player;
+pub use sampler::{History, Sampler, Suspend};
+pub use series::{Lod, SampleStore, Segments, Series};
+pub use taxonomy::{Rgb, SensorGroup, SensorItem};
+
+/// This machine's name, as recorded and shown for remote agents.
+pub fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname").map(|s| s.trim().to_string()).ok().filter(|s| !s.is_empty()).unwrap_or_else(|| "localhost".into())
+}
+
+/// Resident memory of this process in bytes, from `/proc/self/statm`.
+pub fn self_rss() -> Option<u64> {
+    let pages: u64 = std::fs::read_to_string("/proc/self/statm").ok()?.split_whitespace().nth(1)?.parse().ok()?;
+    // SAFETY: sysconf has no preconditions
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    Some(pages * if page > 0 { page as u64 } else { 4096 })
+}
+
+/// `$XDG_CONFIG_HOME/sia`, falling back to `~/.config/sia`.
+pub fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()).map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .map(|d| d.join("sia"))
+}
@@ -0,0 +1,177 @@
+//! Prometheus exporter: `GET /metrics` in the Prometheus text format (0.0.4), with every channel labelled by
+//! sia's taxonomy instead of raw hwmon names:
+//!
+//! ```text
+//! sia_temperature_celsius{id="coretemp@platform/coretemp.0/temp1",group="CPU",sensor="CPU (Package)",chip="coretemp",device="platform/coretemp.0"} 45
+//! ```
+//!
+//! Families are `sia_temperature_celsius`, `sia_frequency_hertz`, `sia_utilization_ratio` (0..1) and the
+//! per-group `sia_temperature_threshold_celsius{level="warn"|"hot"}`. A failed read leaves its series out of
+//! that scrape. The server is a single thread answering one connection at a time, enough for scrapers.
+use crate::collector::{Channel, Kind, Snapshot};
+use crate::taxonomy::SensorGroup;
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+struct Latest { values: Vec<Option<f64>>, samples: u64, wall: SystemTime }
+
+/// Handle to a running exporter; clones share it. Feed it every sample with [`Exporter::update`].
+#[derive(Clone)]
+pub struct Exporter {
+    latest: Arc<Mutex<Latest>>,
+    addr: SocketAddr,
+}
+
+impl Exporter {
+    /// Binds `addr` and starts serving `channels` (grouped as `groups`) on a background thread.
+    pub fn bind(addr: SocketAddr, channels: &[Channel], groups: &[SensorGroup]) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", addr, e)))?;
+        let addr = listener.local_addr()?;
+        let latest = Arc::new(Mutex::new(Latest { values: vec![None; channels.len()], samples: 0, wall: UNIX_EPOCH }));
+        let labels: Vec<String> = channels.iter().map(|c| labels(c, groups)).collect();
+        let kinds: Vec<Kind> = channels.iter().map(|c| c.kind).collect();
+        let thresholds: Vec<(String, f64, f64)> = groups.iter().map(|g| (format!("group=\"{}\",key=\"{}\"", escape(&g.display), escape(&g.key)), g.warn, g.hot)).collect();
+        let shared = latest.clone();
+        thread::Builder::new().name("sia-metrics".into()).spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let body = {
+                    let l = shared.lock().unwrap_or_else(|e| e.into_inner());
+                    render(&l, &labels, &kinds, &thresholds)
+                };
+                let _ = respond(stream, &body);
+            }
+        })?;
+        Ok(Self { latest, addr })
+    }
+
+    /// The address actually bound (useful with port 0).
+    pub fn addr(&self) -> SocketAddr { self.addr }
+
+    pub fn update(&self, snap: &Snapshot) {
+        let mut l = self.latest.lock().unwrap_or_else(|e| e.into_inner());
+        l.values.clone_from(&snap.values);
+        l.samples += 1;
+        l.wall = snap.wall;
+    }
+}
+
+fn escape(s: &str) -> String { s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n") }
+
+fn labels(c: &Channel, groups: &[SensorGroup]) -> String {
+    let group = groups.iter().find(|g| g.key == c.group).map(|g| g.display.as_str()).unwrap_or(&c.group);
+    format!("id=\"{}\",group=\"{}\",sensor=\"{}\",chip=\"{}\",device=\"{}\"", escape(&c.id), escape(group), escape(&c.name), escape(&c.chip), escape(&c.device))
+}
+
+fn render(l: &Latest, labels: &[String], kinds: &[Kind], thresholds: &[(String, f64, f64)]) -> String {
+    let mut out = String::new();
+    let families = [
+        (Kind::Temperature, "sia_temperature_celsius", "Temperature after sensors.conf compute and calibration.", 1.0),
+        (Kind::Frequency, "sia_frequency_hertz", "Clock frequency.", 1e6),
+        (Kind::Utilization, "sia_utilization_ratio", "Utilization, 0 to 1.", 0.01),
+    ];
+    for (kind, name, help, scale) in families {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} gauge", name, help, name);
+        for ((lb, k), v) in labels.iter().zip(kinds).zip(&l.values) {
+            if let (true, Some(v)) = (*k == kind, v) { let _ = writeln!(out, "{}{{{}}} {}", name, lb, v * scale); }
+        }
+    }
+    let _ = writeln!(out, "# HELP sia_temperature_threshold_celsius Warn and hot thresholds of each sensor group.\n# TYPE sia_temperature_threshold_celsius gauge");
+    for (lb, warn, hot) in thresholds {
+        let _ = writeln!(out, "sia_temperature_threshold_celsius{{{},level=\"warn\"}} {}", lb, warn);
+        let _ = writeln!(out, "sia_temperature_threshold_celsius{{{},level=\"hot\"}} {}", lb, hot);
+    }
+    let _ = writeln!(out, "# HELP sia_samples_total Samples taken since sia started.\n# TYPE sia_samples_total counter\nsia_samples_total {}", l.samples);
+    if let Ok(t) = l.wall.duration_since(UNIX_EPOCH) {
+        let _ = writeln!(out, "# HELP sia_last_sample_timestamp_seconds Wall-clock time of the latest sample.\n# TYPE sia_last_sample_timestamp_seconds gauge\nsia_last_sample_timestamp_seconds {:.3}", t.as_secs_f64());
+    }
+    out
+}
+
+/// Answers one request and closes the connection.
+fn respond(mut s: TcpStream, metrics: &str) -> io::Result<()> {
+    s.set_read_timeout(Some(Duration::from_secs(5)))?;
+    s.set_write_timeout(Some(Duration::from_secs(5)))?;
+    // only the request line matters; read until the end of the headers (or 8 KiB)
+    let mut req = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !req.windows(4).any(|w| w == b"\r\n\r\n") && req.len() < 8192 {
+        let n = s.read(&mut buf)?;
+        if n == 0 { break; }
+        req.extend_from_slice(&buf[..n]);
+    }
+    let line = String::from_utf8_lossy(&req);
+    let mut parts = line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let (status, ctype, body) = match (method, path.split('?').next().unwrap_or("")) {
+        ("GET" | "HEAD", "/metrics") => ("200 OK", "text/plain; version=0.0.4; charset=utf-8", metrics),
+        ("GET" | "HEAD", "/") => ("200 OK", "text/html; charset=utf-8", "<html><body><a href=\"/metrics\">Metrics</a></body></html>\n"),
+        ("GET" | "HEAD", _) => ("404 Not Found", "text/plain; charset=utf-8", "not found\n"),
+        _ => ("405 Method Not Allowed", "text/plain; charset=utf-8", "method not allowed\n"),
+    };
+    write!(s, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, ctype, body.len())?;
+    if method != "HEAD" { s.write_all(body.as_bytes())?; }
+    s.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rules;
+    use crate::taxonomy::Rgb;
+    use std::time::Instant;
+
+    fn get(addr: SocketAddr, req: &str) -> String {
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        s.write_all(req.as_bytes()).unwrap();
+        let mut resp = String::new();
+        s.read_to_string(&mut resp).unwrap();
+        resp
+    }
+
+    #[test]
+    fn scrape() {
+        let key = "board@platform/nct6775.656";
+        let channels = [
+            Channel { id: "nct6798@platform/nct6775.656/temp1".into(), name: "SYSTIN \"board\"\\1".into(), kind: Kind::Temperature, group: key.into(), chip: "nct6798".into(), device: "platform/nct6775.656".into(), color: Rgb::WHITE },
+            Channel { id: "cpu0/freq".into(), name: "CPU Core 0".into(), kind: Kind::Frequency, group: "cpu".into(), chip: "cpufreq".into(), device: "system/cpu/cpu0".into(), color: Rgb::WHITE },
+            Channel { id: "cpu/util".into(), name: "CPU".into(), kind: Kind::Utilization, group: "util".into(), chip: "sysinfo".into(), device: String::new(), color: Rgb::WHITE },
+        ];
+        let mut group = SensorGroup::empty(key.into(), "nct6798", &Rules::load(None));
+        group.display = "Board\nline".into();
+        let e = Exporter::bind("127.0.0.1:0".parse().unwrap(), &channels, &[group]).unwrap();
+        e.update(&Snapshot { mono: Instant::now(), boot: Duration::ZERO, wall: UNIX_EPOCH + Duration::from_secs(1_748_772_000), values: vec![Some(45.5), Some(3400.0), None], raw: vec![] });
+
+        let resp = get(e.addr(), "GET /metrics HTTP/1.1\r\nHost: x\r\n\r\n");
+        let (head, body) = resp.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+        assert!(head.contains("Content-Type: text/plain; version=0.0.4"));
+        assert!(head.contains(&format!("Content-Length: {}\r\n", body.len())));
+        for line in [
+            "# HELP sia_temperature_celsius Temperature after sensors.conf compute and calibration.",
+            "# TYPE sia_temperature_celsius gauge",
+            // quotes, backslashes and newlines escaped
+            r#"sia_temperature_celsius{id="nct6798@platform/nct6775.656/temp1",group="Board\nline",sensor="SYSTIN \"board\"\\1",chip="nct6798",device="platform/nct6775.656"} 45.5"#,
+            "# TYPE sia_frequency_hertz gauge",
+            r#"sia_frequency_hertz{id="cpu0/freq",group="cpu",sensor="CPU Core 0",chip="cpufreq",device="system/cpu/cpu0"} 3400000000"#,
+            "# TYPE sia_utilization_ratio gauge",
+            r#"sia_temperature_threshold_celsius{group="Board\nline",key="board@platform/nct6775.656",level="hot"} 100"#,
+            "# TYPE sia_samples_total counter",
+            "sia_samples_total 1",
+            "sia_last_sample_timestamp_seconds 1748772000.000",
+        ] {
+            assert!(body.lines().any(|l| l == line), "missing {:?} in\n{}", line, body);
+        }
+        // the failed utilization read is left out
+        assert!(!body.contains("sia_utilization_ratio{"));
+
+        assert!(get(e.addr(), "GET /nope HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404 "));
+        assert!(get(e.addr(), "POST /metrics HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 405 "));
+        let head = get(e.addr(), "HEAD /metrics HTTP/1.1\r\n\r\n");
+        assert!(head.starts_with("HTTP/1.1 200 OK") && head.ends_with("\r\n\r\n"));
+    }
+}
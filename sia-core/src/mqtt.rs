@@ -0,0 +1,419 @@
+//! MQTT publishing (3.1.1, QoS 0, plain TCP). Topics are derived from host, group and sensor:
+//!
+//! * `<prefix>/<host>/<group>/<sensor>`: each reading as a bare number (per-sensor mode), with a retained
+//!   `.../meta` JSON carrying id, name, unit, chip, device and, for temperatures, the warn/hot thresholds;
+//! * `<prefix>/<host>/snapshot`: one JSON object per sample keyed by sensor id (snapshot mode);
+//! * `<prefix>/<host>/status`: retained `online`, and `offline` as the last will.
+//!
+//! With discovery on, retained Home Assistant `sensor` configs are published under the discovery prefix so
+//! every channel shows up as an entity of one device per host. Segments are lowercased with anything other
+//! than `[a-z0-9_-]` replaced by `_`. The keepalive covers at least three sample periods. A ping goes out
+//! every half keepalive, and a broker that has not answered it within the keepalive is taken as gone: the
+//! connection is dropped and rebuilt like after a failed write.
+use crate::collector::{Channel, Kind, Snapshot};
+use crate::push::{connect_tcp, Sink, NET_TIMEOUT};
+use crate::record::round;
+use crate::taxonomy::SensorGroup;
+use serde_json::{json, Map, Value};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::str::FromStr;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+const DEFAULT_PORT: u16 = 1883;
+/// Shortest keepalive; longer sample periods get a longer one.
+const KEEPALIVE_MIN: Duration = Duration::from_secs(60);
+/// How long to wait before reconnecting to a broker that dropped.
+const RETRY: Duration = Duration::from_secs(5);
+
+/// `mqtt://[USER[:PASS]@]HOST[:PORT][/PREFIX]`.
+#[derive(Clone, Debug)]
+pub struct MqttConfig {
+    pub broker: String,
+    pub user: Option<String>,
+    pub pass: Option<String>,
+    pub prefix: String,
+    /// One JSON message per sample instead of one message per sensor.
+    pub snapshot: bool,
+    /// Home Assistant discovery prefix, usually `homeassistant`.
+    pub discovery: Option<String>,
+    /// Time between samples, which the keepalive is sized for.
+    pub period: Duration,
+}
+
+impl FromStr for MqttConfig {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        let rest = s.strip_prefix("mqtt://").ok_or_else(|| format!("{}: expected mqtt://[USER[:PASS]@]HOST[:PORT][/PREFIX]", s))?;
+        let (auth, rest) = match rest.rsplit_once('@') { Some((a, r)) => (Some(a), r), None => (None, rest) };
+        let (hostport, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if hostport.is_empty() { return Err(format!("{}: no broker host", s)); }
+        let broker = if hostport.rsplit_once(':').is_some_and(|(_, p)| p.parse::<u16>().is_ok()) { hostport.to_string() } else { format!("{}:{}", hostport, DEFAULT_PORT) };
+        let (user, pass) = match auth.map(|a| a.split_once(':').map(|(u, p)| (u, Some(p))).unwrap_or((a, None))) {
+            Some((u, p)) => (Some(u.to_string()), p.map(String::from)),
+            None => (None, None),
+        };
+        let prefix = prefix.trim_matches('/');
+        Ok(Self { broker, user, pass, prefix: if prefix.is_empty() { "sia".into() } else { prefix.into() }, snapshot: false, discovery: None, period: Duration::from_secs(1) })
+    }
+}
+
+/// Topic segment: lowercase, `[a-z0-9_-]` only.
+fn slug(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() || c == '-' { out.push(c); } else if !out.ends_with('_') { out.push('_'); }
+    }
+    let out = out.trim_matches('_').to_string();
+    if out.is_empty() { "_".into() } else { out }
+}
+
+struct Sensor { id: String, topic: String }
+
+pub struct Publisher {
+    config: MqttConfig,
+    base: String,
+    sensors: Vec<Sensor>,
+    /// Retained messages sent on every (re)connect: status, metadata, discovery.
+    retained: Vec<(String, Vec<u8>)>,
+    conn: Option<TcpStream>,
+    keepalive: Duration,
+    /// When the last PINGREQ went out, and whether its PINGRESP is still missing.
+    last_ping: Instant,
+    ping_sent: Option<Instant>,
+    /// Bytes read from the broker that do not make up a whole packet yet.
+    inbox: Vec<u8>,
+    retry_at: Option<Instant>,
+    last_error: Option<String>,
+}
+
+impl Publisher {
+    /// Connects to the broker right away, so a bad address or login is reported before sampling starts.
+    pub fn new(config: MqttConfig, channels: &[Channel], groups: &[SensorGroup]) -> io::Result<Self> {
+        let host = slug(&crate::hostname());
+        let base = format!("{}/{}", config.prefix, host);
+        let status = format!("{}/status", base);
+        let snapshot_topic = format!("{}/snapshot", base);
+        let mut sensors: Vec<Sensor> = vec![];
+        let mut retained = vec![(status.clone(), b"online".to_vec())];
+        for c in channels {
+            let g = groups.iter().find(|g| g.key == c.group);
+            let group = g.map(|g| slug(&g.display)).unwrap_or_else(|| slug(&c.group));
+            let mut topic = format!("{}/{}/{}", base, group, slug(&c.name));
+            if sensors.iter().any(|s| s.topic == topic) { topic = format!("{}_{}", topic, sensors.len()); }
+            let mut meta = json!({ "id": c.id, "name": c.name, "unit": c.kind.unit(), "group": g.map(|g| g.display.as_str()).unwrap_or(&c.group), "chip": c.chip, "device": c.device });
+            if let (Kind::Temperature, Some(g)) = (c.kind, g) { meta["warn"] = json!(g.warn); meta["hot"] = json!(g.hot); }
+            retained.push((format!("{}/meta", topic), meta.to_string().into_bytes()));
+            if let Some(disc) = &config.discovery {
+                let object = slug(&c.id);
+                let mut cfg = json!({
+                    "name": match g { Some(g) => format!("{} {}", g.display, c.name), None => c.name.clone() },
+                    "unique_id": format!("sia_{}_{}", host, object),
+                    "unit_of_measurement": c.kind.unit(),
+                    "state_class": "measurement",
+                    "availability_topic": status,
+                    "device": { "identifiers": [format!("sia_{}", host)], "name": crate::hostname(), "manufacturer": "sia" },
+                });
+                match c.kind { Kind::Temperature => cfg["device_class"] = json!("temperature"), Kind::Frequency => cfg["device_class"] = json!("frequency"), Kind::Utilization => {} }
+                if config.snapshot {
+                    cfg["state_topic"] = json!(snapshot_topic);
+                    cfg["value_template"] = json!(format!("{{{{ value_json.values['{}'] }}}}", c.id));
+                } else {
+                    cfg["state_topic"] = json!(topic);
+                }
+                retained.push((format!("{}/sensor/sia_{}/{}/config", disc, host, object), cfg.to_string().into_bytes()));
+            }
+            sensors.push(Sensor { id: c.id.clone(), topic });
+        }
+        let keepalive = (config.period * 3).max(KEEPALIVE_MIN).min(Duration::from_secs(u16::MAX as u64));
+        let mut p = Self { config, base, sensors, retained, conn: None, keepalive, last_ping: Instant::now(), ping_sent: None, inbox: vec![], retry_at: None, last_error: None };
+        p.conn = Some(p.connect()?);
+        Ok(p)
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let with_broker = |e: io::Error| io::Error::new(e.kind(), format!("mqtt://{}: {}", self.config.broker, e));
+        let mut s = connect_tcp(&self.config.broker).map_err(with_broker)?;
+        s.set_read_timeout(Some(NET_TIMEOUT))?;
+        s.write_all(&connect_packet(&self.config, &format!("{}/status", self.base), self.keepalive.as_secs() as u16)).map_err(with_broker)?;
+        let mut ack = [0u8; 4];
+        s.read_exact(&mut ack).map_err(with_broker)?;
+        if ack[0] != 0x20 || ack[1] != 2 { return Err(with_broker(io::Error::new(io::ErrorKind::InvalidData, "not an MQTT broker"))); }
+        if ack[3] != 0 {
+            let why = match ack[3] { 1 => "protocol version refused", 2 => "client id rejected", 3 => "server unavailable", 4 => "bad user name or password", 5 => "not authorized", _ => "connection refused" };
+            return Err(with_broker(io::Error::new(io::ErrorKind::PermissionDenied, why)));
+        }
+        for (topic, payload) in &self.retained { s.write_all(&publish_packet(topic, payload, true)).map_err(with_broker)?; }
+        Ok(s)
+    }
+
+    fn send(&mut self, snap: &Snapshot) -> io::Result<()> {
+        if self.conn.is_none() {
+            if self.retry_at.is_some_and(|t| Instant::now() < t) { return Ok(()); }
+            self.retry_at = Some(Instant::now() + RETRY);
+            self.conn = Some(self.connect()?);
+            (self.last_ping, self.ping_sent) = (Instant::now(), None);
+            self.inbox.clear();
+        }
+        let mut out = vec![];
+        if self.config.snapshot {
+            let values: Map<String, Value> = self.sensors.iter().zip(&snap.values).map(|(s, v)| (s.id.clone(), v.map(round).map(Value::from).unwrap_or(Value::Null))).collect();
+            let unix_time = snap.wall.duration_since(UNIX_EPOCH).map(|d| round(d.as_secs_f64())).unwrap_or(0.0);
+            out.extend(publish_packet(&format!("{}/snapshot", self.base), json!({ "unix_time": unix_time, "values": values }).to_string().as_bytes(), false));
+        } else {
+            for (s, v) in self.sensors.iter().zip(&snap.values) {
+                if let Some(v) = v { out.extend(publish_packet(&s.topic, round(*v).to_string().as_bytes(), false)); }
+            }
+        }
+        // also keeps the connection alive on the broker's side when no sample publishes anything
+        if self.ping_sent.is_none() && self.last_ping.elapsed() >= self.keepalive / 2 {
+            out.extend([0xC0, 0x00]);
+            (self.last_ping, self.ping_sent) = (Instant::now(), Some(Instant::now()));
+        }
+        if !out.is_empty() {
+            let conn = self.conn.as_mut().expect("connected above");
+            conn.write_all(&out)?;
+            conn.flush()?;
+        }
+        self.drain()?;
+        if self.ping_sent.is_some_and(|t| t.elapsed() >= self.keepalive) {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "no PINGRESP within the keepalive"));
+        }
+        Ok(())
+    }
+
+    /// Reads whatever the broker sent without waiting for more. sia subscribes to nothing, so the only packet
+    /// that matters is PINGRESP; a closed connection is an error.
+    fn drain(&mut self) -> io::Result<()> {
+        let conn = self.conn.as_mut().expect("connected");
+        conn.set_nonblocking(true)?;
+        let mut buf = [0u8; 1024];
+        let res = loop {
+            match conn.read(&mut buf) {
+                Ok(0) => break Err(io::Error::new(io::ErrorKind::UnexpectedEof, "broker closed the connection")),
+                Ok(n) => self.inbox.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        conn.set_nonblocking(false)?;
+        res?;
+        while let Some((kind, len)) = packet_len(&self.inbox)? {
+            if kind == 0xD0 { self.ping_sent = None; }
+            self.inbox.drain(..len);
+        }
+        Ok(())
+    }
+}
+
+impl Sink for Publisher {
+    /// Publishes one sample. A broker that dropped is retried every few seconds; samples in between are lost.
+    fn write(&mut self, snap: &Snapshot) -> io::Result<()> {
+        let res = self.send(snap);
+        match &res {
+            Ok(()) => if self.conn.is_some() { self.last_error = None; },
+            Err(e) => { self.conn = None; self.last_error = Some(format!("mqtt://{}: {}", self.config.broker, e)); }
+        }
+        res
+    }
+
+    fn last_error(&self) -> Option<&str> { self.last_error.as_deref() }
+}
+
+impl Drop for Publisher {
+    /// A clean DISCONNECT does not fire the will, so mark the host offline first.
+    fn drop(&mut self) {
+        if let Some(s) = self.conn.as_mut() {
+            let _ = s.write_all(&publish_packet(&format!("{}/status", self.base), b"offline", true));
+            let _ = s.write_all(&[0xE0, 0x00]);
+        }
+    }
+}
+
+fn remaining_length(mut n: usize, out: &mut Vec<u8>) {
+    loop {
+        let mut b = (n % 128) as u8;
+        n /= 128;
+        if n > 0 { b |= 0x80; }
+        out.push(b);
+        if n == 0 { break; }
+    }
+}
+
+/// Type byte and total length of the first packet in `buf`, once all of it is there.
+fn packet_len(buf: &[u8]) -> io::Result<Option<(u8, usize)>> {
+    let mut n = 0;
+    for (i, b) in buf.iter().skip(1).take(4).enumerate() {
+        n |= ((b & 0x7F) as usize) << (7 * i);
+        if b & 0x80 == 0 { return Ok(Some((buf[0], 2 + i + n)).filter(|(_, len)| *len <= buf.len())); }
+    }
+    if buf.len() > 4 { Err(io::Error::new(io::ErrorKind::InvalidData, "bad packet length from broker")) } else { Ok(None) }
+}
+
+fn string(s: &[u8], out: &mut Vec<u8>) {
+    out.extend((s.len() as u16).to_be_bytes());
+    out.extend(s);
+}
+
+fn connect_packet(c: &MqttConfig, will_topic: &str, keepalive_secs: u16) -> Vec<u8> {
+    // clean session, retained QoS 0 will
+    let mut flags = 0x02 | 0x04 | 0x20;
+    if c.user.is_some() { flags |= 0x80; }
+    if c.pass.is_some() { flags |= 0x40; }
+    let mut body = vec![];
+    string(b"MQTT", &mut body);
+    body.push(4);
+    body.push(flags);
+    body.extend(keepalive_secs.to_be_bytes());
+    string(format!("sia-{}-{}", slug(&crate::hostname()), std::process::id()).as_bytes(), &mut body);
+    string(will_topic.as_bytes(), &mut body);
+    string(b"offline", &mut body);
+    if let Some(u) = &c.user { string(u.as_bytes(), &mut body); }
+    if let Some(p) = &c.pass { string(p.as_bytes(), &mut body); }
+    let mut out = vec![0x10];
+    remaining_length(body.len(), &mut out);
+    out.extend(body);
+    out
+}
+
+fn publish_packet(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = vec![];
+    string(topic.as_bytes(), &mut body);
+    body.extend(payload);
+    let mut out = vec![0x30 | retain as u8];
+    remaining_length(body.len(), &mut out);
+    out.extend(body);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::taxonomy::Rgb;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::{self, Receiver};
+    use std::sync::Arc;
+    use std::thread;
+
+    /// (connection number, packet type byte, packet body)
+    type Packet = (usize, u8, Vec<u8>);
+
+    fn read_packet(s: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+        let mut head = [0u8; 1];
+        s.read_exact(&mut head).ok()?;
+        let (mut len, mut shift) = (0, 0);
+        loop {
+            let mut b = [0u8; 1];
+            s.read_exact(&mut b).ok()?;
+            len |= ((b[0] & 0x7F) as usize) << shift;
+            shift += 7;
+            if b[0] & 0x80 == 0 { break; }
+        }
+        let mut body = vec![0; len];
+        s.read_exact(&mut body).ok()?;
+        Some((head[0], body))
+    }
+
+    /// Accepts any CONNECT and answers PINGREQ while `pong` is set; every packet received is forwarded.
+    fn broker(pong: Arc<AtomicBool>) -> (String, Receiver<Packet>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || for (n, s) in listener.incoming().enumerate() {
+            let (mut s, tx, pong) = (s.unwrap(), tx.clone(), pong.clone());
+            thread::spawn(move || while let Some((kind, body)) = read_packet(&mut s) {
+                match kind {
+                    0x10 => s.write_all(&[0x20, 2, 0, 0]).unwrap(),
+                    0xC0 if pong.load(Ordering::Relaxed) => s.write_all(&[0xD0, 0]).unwrap(),
+                    _ => {}
+                }
+                if tx.send((n, kind, body)).is_err() { break; }
+            });
+        });
+        (addr, rx)
+    }
+
+    fn next(rx: &Receiver<Packet>) -> Packet { rx.recv_timeout(NET_TIMEOUT).expect("packet from sia") }
+
+    /// Topic and payload of a PUBLISH body.
+    fn publish(body: &[u8]) -> (String, String) {
+        let n = u16::from_be_bytes([body[0], body[1]]) as usize;
+        (String::from_utf8_lossy(&body[2..2 + n]).into_owned(), String::from_utf8_lossy(&body[2 + n..]).into_owned())
+    }
+
+    fn snap(v: Option<f64>) -> Snapshot {
+        Snapshot { mono: Instant::now(), boot: Duration::ZERO, wall: UNIX_EPOCH, values: vec![v], raw: vec![v] }
+    }
+
+    #[test]
+    fn connect_publish_and_reconnect() {
+        let pong = Arc::new(AtomicBool::new(true));
+        let (addr, rx) = broker(pong.clone());
+        let config: MqttConfig = format!("mqtt://bob:pw@{}/test", addr).parse().unwrap();
+        let channels = [Channel { id: "coretemp@platform/coretemp.0/temp1".into(), name: "CPU (Package)".into(), kind: Kind::Temperature, group: "cpu".into(), chip: "coretemp".into(), device: "platform/coretemp.0".into(), color: Rgb::WHITE }];
+        let mut p = Publisher::new(config, &channels, &[]).unwrap();
+        let status = format!("test/{}/status", slug(&crate::hostname()));
+
+        // CONNECT: MQTT 3.1.1, clean session, user and password, retained "offline" will on the status topic
+        let (conn, kind, body) = next(&rx);
+        assert_eq!((conn, kind), (0, 0x10));
+        assert_eq!(&body[..7], b"\x00\x04MQTT\x04");
+        assert_eq!(body[7], 0x80 | 0x40 | 0x20 | 0x04 | 0x02);
+        assert_eq!(u16::from_be_bytes([body[8], body[9]]), 60);
+        let will = format!("{}{}\x00\x07offline\x00\x03bob\x00\x02pw", String::from_utf8_lossy(&(status.len() as u16).to_be_bytes()), status);
+        assert!(body.ends_with(will.as_bytes()));
+
+        // retained status and metadata, then the reading itself
+        let (_, kind, body) = next(&rx);
+        assert_eq!((kind, publish(&body)), (0x31, (status.clone(), "online".into())));
+        let (_, kind, body) = next(&rx);
+        assert_eq!((kind, publish(&body).0), (0x31, format!("test/{}/cpu/cpu_package/meta", slug(&crate::hostname()))));
+        p.write(&snap(Some(45.04))).unwrap();
+        let (_, kind, body) = next(&rx);
+        assert_eq!((kind, publish(&body)), (0x30, (format!("test/{}/cpu/cpu_package", slug(&crate::hostname())), "45.04".into())));
+
+        // an answered ping keeps the connection
+        p.keepalive = Duration::from_millis(400);
+        thread::sleep(Duration::from_millis(250));
+        p.write(&snap(None)).unwrap();
+        assert_eq!(next(&rx).1, 0xC0);
+        thread::sleep(Duration::from_millis(50));
+        p.write(&snap(None)).unwrap();
+        assert!(p.ping_sent.is_none());
+
+        // an unanswered one drops it, and the next write reconnects
+        pong.store(false, Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(250));
+        p.write(&snap(None)).unwrap();
+        assert_eq!(next(&rx).1, 0xC0);
+        thread::sleep(Duration::from_millis(450));
+        let e = p.write(&snap(None)).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+        assert!(p.last_error().unwrap().contains("PINGRESP"));
+        p.retry_at = None;
+        p.write(&snap(Some(46.0))).unwrap();
+        assert!(p.last_error().is_none());
+        let (conn, kind, _) = next(&rx);
+        assert_eq!((conn, kind), (1, 0x10));
+        assert_eq!(publish(&next(&rx).2), (status.clone(), "online".into()));
+
+        // leaving marks the host offline before disconnecting, since a clean DISCONNECT skips the will
+        drop(p);
+        let rest: Vec<Packet> = rx.iter().take_while(|(_, kind, _)| *kind != 0xE0).collect();
+        assert_eq!(rest.last().map(|(_, _, body)| publish(body)), Some((status, "offline".into())));
+    }
+
+    #[test]
+    fn packet_lengths() {
+        assert_eq!(packet_len(&[0xD0, 0]).unwrap(), Some((0xD0, 2)));
+        assert_eq!(packet_len(&[0xD0]).unwrap(), None);
+        assert_eq!(packet_len(&[0x30, 3, 0, 1]).unwrap(), None);
+        assert_eq!(packet_len(&[0x30, 0x80, 0x01]).unwrap(), None);
+        assert!(packet_len(&[0x30, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F]).is_err());
+        let mut out = vec![];
+        remaining_length(321, &mut out);
+        assert_eq!(out, [0xC1, 0x02]);
+    }
+}
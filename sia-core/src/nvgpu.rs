@@ -0,0 +1,47 @@
+//! NVIDIA GPU utilization, VRAM, temperature and clocks through NVML (first GPU).
+use nvml_wrapper::{
+    enum_wrappers::device::{Clock as NvClock, TemperatureSensor},
+    Nvml,
+};
+
+pub struct NvState {
+    pub nvml: Nvml,
+    pub device_index: u32,
+}
+
+impl NvState {
+    pub fn try_new() -> Option<Self> {
+        let nvml = Nvml::init().ok()?;
+        let count = nvml.device_count().ok()?;
+        if count == 0 { return None; }
+        let idx = 0u32;
+        let _ = nvml.device_by_index(idx).ok()?; // probe
+        Some(Self { nvml, device_index: idx })
+    }
+}
+
+/// Returns (GPU %, VRAM %, °C)
+pub fn first_gpu_metrics(state: &NvState) -> Option<(f64, f64, f64)> {
+    let dev = state.nvml.device_by_index(state.device_index).ok()?;
+    let util = dev.utilization_rates().ok()?; // gpu, mem (% u32)
+    let mem = dev.memory_info().ok()?; // bytes
+    let temp = dev.temperature(TemperatureSensor::Gpu).ok()? as f64; // °C
+    let mem_pct = if mem.total > 0 { (mem.used as f64 / mem.total as f64) * 100.0 } else { 0.0 };
+    Some((util.gpu as f64, mem_pct, temp))
+}
+
+/// PCI address in sysfs form (`0000:01:00.0`); NVML pads the domain to 8 digits.
+pub fn bus_id(state: &NvState) -> Option<String> {
+    let bus = state.nvml.device_by_index(state.device_index).and_then(|d| d.pci_info()).ok()?.bus_id.to_lowercase();
+    Some(if bus.len() == 16 && bus.starts_with("0000") { bus[4..].to_string() } else { bus })
+}
+
+/// Returns clocks in MHz: (graphics, sm, memory, video)
+pub fn gpu_clocks_mhz(state: &NvState) -> Option<(f64, f64, f64, f64)> {
+    let dev = state.nvml.device_by_index(state.device_index).ok()?;
+    let g = dev.clock_info(NvClock::Graphics).ok()? as f64;
+    let sm = dev.clock_info(NvClock::SM).ok().map(|v| v as f64).unwrap_or(g);
+    let m = dev.clock_info(NvClock::Memory).ok()? as f64;
+    let v = dev.clock_info(NvClock::Video).ok().map(|v| v as f64).unwrap_or(g);
+    Some((g, sm, m, v))
+}
@@ -0,0 +1,336 @@
+//! Push output: samples as InfluxDB line protocol or Graphite plaintext, batched and sent to a UDP, TCP or
+//! Unix socket, or written to stdout.
+//!
+//! ```text
+//! sia_temperature,id=coretemp@platform/coretemp.0/temp1,group=CPU,sensor=CPU\ (Package),chip=coretemp,device=platform/coretemp.0 value=45 1748772000000000000
+//! sia.temperature.CPU.CPU__Package_.coretemp_platform_coretemp_0_temp1 45 1748772000
+//! ```
+//!
+//! Measurements are `sia_temperature` (°C), `sia_frequency` (MHz) and `sia_utilization` (%); a failed read
+//! is left out. A batch that cannot be sent is dropped and the connection is retried at the next flush.
+use crate::collector::{Channel, Kind, Snapshot};
+use crate::record::round;
+use crate::taxonomy::SensorGroup;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target { Udp(String), Tcp(String), Unix(PathBuf), Stdout }
+
+impl FromStr for Target {
+    type Err = String;
+    /// `udp://HOST:PORT`, `tcp://HOST:PORT`, `unix:///PATH` or `-` for stdout.
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.split_once("://") {
+            _ if s == "-" || s == "stdout" => Ok(Target::Stdout),
+            Some(("udp", a)) if !a.is_empty() => Ok(Target::Udp(a.into())),
+            Some(("tcp", a)) if !a.is_empty() => Ok(Target::Tcp(a.into())),
+            Some(("unix", p)) if !p.is_empty() => Ok(Target::Unix(p.into())),
+            _ => Err(format!("{}: expected udp://HOST:PORT, tcp://HOST:PORT, unix:///PATH or -", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self { Target::Udp(a) => write!(f, "udp://{}", a), Target::Tcp(a) => write!(f, "tcp://{}", a), Target::Unix(p) => write!(f, "unix://{}", p.display()), Target::Stdout => f.write_str("-") }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineFormat { Influx, Graphite }
+
+impl FromStr for LineFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s { "influx" => Ok(LineFormat::Influx), "graphite" => Ok(LineFormat::Graphite), _ => Err(format!("{}: expected influx or graphite", s)) }
+    }
+}
+
+/// An output that samples are streamed to, which may fail and recover by itself: [`Pusher`] and
+/// [`crate::mqtt::Publisher`].
+pub trait Sink: Send {
+    /// Takes one sample; returns the error if sending failed during this call.
+    fn write(&mut self, snap: &Snapshot) -> io::Result<()>;
+    /// Why sending last failed; cleared once it works again.
+    fn last_error(&self) -> Option<&str>;
+    /// Sends anything held back for batching.
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+/// Samples the sinks may fall behind by; samples beyond that are not sent.
+const SINK_QUEUE: usize = 64;
+
+enum SinkMsg { Add(Box<dyn Sink>), Sample(Snapshot) }
+
+/// Sinks run on their own thread, so a slow or unreachable network never delays sampling. Dropping it
+/// flushes whatever the sinks still batch and waits for that to finish.
+pub struct SinkThread { tx: Option<SyncSender<SinkMsg>>, handle: Option<JoinHandle<()>> }
+
+impl SinkThread {
+    /// `on_error` is called after every sample with the first sink's error, or `None` when all of them worked.
+    pub fn spawn(mut on_error: impl FnMut(Option<String>) + Send + 'static) -> Self {
+        let (tx, rx) = mpsc::sync_channel(SINK_QUEUE);
+        let handle = thread::Builder::new().name("sia-sinks".into()).spawn(move || {
+            let mut sinks: Vec<Box<dyn Sink>> = vec![];
+            for msg in rx {
+                match msg {
+                    SinkMsg::Add(s) => sinks.push(s),
+                    SinkMsg::Sample(snap) => {
+                        for s in &mut sinks { let _ = s.write(&snap); }
+                        on_error(sinks.iter().find_map(|s| s.last_error().map(String::from)));
+                    }
+                }
+            }
+            for s in &mut sinks { let _ = s.flush(); }
+        }).expect("spawning sink thread");
+        Self { tx: Some(tx), handle: Some(handle) }
+    }
+
+    /// Takes samples from the next [`SinkThread::send`] on.
+    pub fn add(&self, sink: Box<dyn Sink>) { if let Some(tx) = &self.tx { let _ = tx.send(SinkMsg::Add(sink)); } }
+
+    /// Queues one sample, or drops it when the sinks are [`SINK_QUEUE`] samples behind.
+    pub fn send(&self, snap: &Snapshot) { if let Some(tx) = &self.tx { let _ = tx.try_send(SinkMsg::Sample(snap.clone())); } }
+}
+
+impl Drop for SinkThread {
+    fn drop(&mut self) {
+        self.tx = None;
+        if let Some(h) = self.handle.take() { let _ = h.join(); }
+    }
+}
+
+/// Where and how to push, and how many seconds of samples to batch into one send.
+#[derive(Clone, Debug)]
+pub struct PushConfig { pub target: Target, pub format: LineFormat, pub every: Duration }
+
+enum Conn { Udp(UdpSocket), Tcp(TcpStream), Unix(UnixStream), Stdout }
+
+/// UDP batches are split at line boundaries to stay within one unfragmented datagram.
+const UDP_MAX: usize = 1400;
+
+/// How long connecting or writing may take before the attempt fails.
+pub(crate) const NET_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connects to `addr` (`HOST:PORT`), trying each address it resolves to for at most [`NET_TIMEOUT`], and sets
+/// the write timeout.
+pub(crate) fn connect_tcp(addr: &str) -> io::Result<TcpStream> {
+    let mut last = io::Error::new(io::ErrorKind::NotFound, "no address");
+    for a in addr.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&a, NET_TIMEOUT) {
+            Ok(s) => { s.set_write_timeout(Some(NET_TIMEOUT))?; return Ok(s); }
+            Err(e) => last = e,
+        }
+    }
+    Err(last)
+}
+
+pub struct Pusher {
+    config: PushConfig,
+    /// Series key per channel, without value and timestamp.
+    keys: Vec<String>,
+    conn: Option<Conn>,
+    buf: String,
+    last_flush: Instant,
+    last_error: Option<String>,
+}
+
+impl Pusher {
+    /// Connects to the target right away, so a bad address is reported before sampling starts.
+    pub fn new(config: PushConfig, channels: &[Channel], groups: &[SensorGroup]) -> io::Result<Self> {
+        let keys = channels.iter().map(|c| key(config.format, c, groups)).collect();
+        let mut p = Self { config, keys, conn: None, buf: String::new(), last_flush: Instant::now(), last_error: None };
+        p.conn = Some(p.connect()?);
+        Ok(p)
+    }
+
+    pub fn target(&self) -> &Target { &self.config.target }
+
+    /// Sends whatever is batched. On failure the batch is dropped and the connection closed.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.last_flush = Instant::now();
+        let buf = std::mem::take(&mut self.buf);
+        if buf.is_empty() { return Ok(()); }
+        let res = self.send(&buf);
+        match &res {
+            Ok(()) => self.last_error = None,
+            Err(e) => { self.conn = None; self.last_error = Some(format!("{}: {}", self.config.target, e)); }
+        }
+        res
+    }
+
+    fn send(&mut self, buf: &str) -> io::Result<()> {
+        if self.conn.is_none() { self.conn = Some(self.connect()?); }
+        match self.conn.as_mut().expect("just connected") {
+            Conn::Udp(s) => {
+                let (bytes, mut start) = (buf.as_bytes(), 0);
+                while start < bytes.len() {
+                    // cut after the last whole line that fits; a single longer line goes alone
+                    let window = &bytes[start..bytes.len().min(start + UDP_MAX)];
+                    let end = window.iter().rposition(|b| *b == b'\n').or_else(|| bytes[start..].iter().position(|b| *b == b'\n'))
+                        .map(|i| start + i + 1).unwrap_or(bytes.len());
+                    s.send(&bytes[start..end])?;
+                    start = end;
+                }
+                Ok(())
+            }
+            Conn::Tcp(s) => { s.write_all(buf.as_bytes())?; s.flush() }
+            Conn::Unix(s) => { s.write_all(buf.as_bytes())?; s.flush() }
+            Conn::Stdout => { let mut out = io::stdout().lock(); out.write_all(buf.as_bytes())?; out.flush() }
+        }
+    }
+
+    fn connect(&self) -> io::Result<Conn> {
+        let with_target = |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", self.config.target, e));
+        match &self.config.target {
+            Target::Udp(a) => {
+                let s = UdpSocket::bind(if a.starts_with('[') { "[::]:0" } else { "0.0.0.0:0" }).map_err(with_target)?;
+                s.connect(a).map_err(with_target)?;
+                Ok(Conn::Udp(s))
+            }
+            Target::Tcp(a) => {
+                Ok(Conn::Tcp(connect_tcp(a).map_err(with_target)?))
+            }
+            Target::Unix(p) => {
+                let s = UnixStream::connect(p).map_err(with_target)?;
+                s.set_write_timeout(Some(NET_TIMEOUT))?;
+                Ok(Conn::Unix(s))
+            }
+            Target::Stdout => Ok(Conn::Stdout),
+        }
+    }
+}
+
+impl Sink for Pusher {
+    /// Adds one sample to the batch and sends the batch once it spans the configured interval.
+    fn write(&mut self, snap: &Snapshot) -> io::Result<()> {
+        let t = snap.wall.duration_since(UNIX_EPOCH).unwrap_or_default();
+        for (key, v) in self.keys.iter().zip(&snap.values) {
+            let Some(v) = v.map(round) else { continue };
+            let _ = match self.config.format {
+                LineFormat::Influx => writeln!(self.buf, "{} value={} {}", key, v, t.as_nanos()),
+                LineFormat::Graphite => writeln!(self.buf, "{} {} {}", key, v, t.as_secs()),
+            };
+        }
+        if self.last_flush.elapsed() >= self.config.every { self.flush() } else { Ok(()) }
+    }
+
+    fn last_error(&self) -> Option<&str> { self.last_error.as_deref() }
+    fn flush(&mut self) -> io::Result<()> { Pusher::flush(self) }
+}
+
+fn measurement(kind: Kind) -> &'static str {
+    match kind { Kind::Temperature => "sia_temperature", Kind::Frequency => "sia_frequency", Kind::Utilization => "sia_utilization" }
+}
+
+/// Influx: `measurement,tag=value,...` (empty tags left out, as the protocol requires).
+/// Graphite: `sia.<kind>.<group>.<sensor>.<id>`, each part reduced to `[A-Za-z0-9_-]`; the id keeps sensors
+/// with the same label apart.
+fn key(format: LineFormat, c: &Channel, groups: &[SensorGroup]) -> String {
+    let group = groups.iter().find(|g| g.key == c.group).map(|g| g.display.as_str()).unwrap_or(&c.group);
+    match format {
+        LineFormat::Influx => {
+            let mut k = measurement(c.kind).to_string();
+            for (tag, v) in [("id", c.id.as_str()), ("group", group), ("sensor", &c.name), ("chip", &c.chip), ("device", &c.device)] {
+                if !v.is_empty() { let _ = write!(k, ",{}={}", tag, influx_escape(v)); }
+            }
+            k
+        }
+        LineFormat::Graphite => {
+            let kind = &measurement(c.kind)["sia_".len()..];
+            format!("sia.{}.{}.{}.{}", kind, graphite_part(group), graphite_part(&c.name), graphite_part(&c.id))
+        }
+    }
+}
+
+fn influx_escape(s: &str) -> String { s.replace('\\', "\\\\").replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ") }
+
+fn graphite_part(s: &str) -> String {
+    let p: String = s.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect();
+    if p.is_empty() { "_".into() } else { p }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rules;
+    use crate::taxonomy::Rgb;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    fn channel(id: &str, name: &str, kind: Kind, group: &str, chip: &str, device: &str) -> Channel {
+        Channel { id: id.into(), name: name.into(), kind, group: group.into(), chip: chip.into(), device: device.into(), color: Rgb::WHITE }
+    }
+
+    fn snap(unix_s: u64, values: Vec<Option<f64>>) -> Snapshot {
+        Snapshot { mono: Instant::now(), boot: Duration::ZERO, wall: UNIX_EPOCH + Duration::from_secs(unix_s), raw: values.clone(), values }
+    }
+
+    fn cpu() -> (Vec<Channel>, Vec<SensorGroup>) {
+        let key = "cpu@platform/coretemp.0";
+        let channels = vec![
+            channel("coretemp@platform/coretemp.0/temp1", "CPU (Package)", Kind::Temperature, key, "coretemp", "platform/coretemp.0"),
+            channel("coretemp@platform/coretemp.0/temp2", "CPU (Package)", Kind::Temperature, key, "coretemp", "platform/coretemp.0"),
+            channel("cpu/util", "CPU", Kind::Utilization, "util", "sysinfo", ""),
+        ];
+        (channels, vec![SensorGroup::empty(key.into(), "coretemp", &Rules::load(None))])
+    }
+
+    #[test]
+    fn influx_over_udp() {
+        let rx = UdpSocket::bind("127.0.0.1:0").unwrap();
+        rx.set_read_timeout(Some(NET_TIMEOUT)).unwrap();
+        let (channels, groups) = cpu();
+        let config = PushConfig { target: Target::Udp(rx.local_addr().unwrap().to_string()), format: LineFormat::Influx, every: Duration::ZERO };
+        let mut p = Pusher::new(config, &channels, &groups).unwrap();
+        p.write(&snap(1_748_772_000, vec![Some(45.0), None, Some(12.3456)])).unwrap();
+        let mut buf = [0; UDP_MAX];
+        let n = rx.recv(&mut buf).unwrap();
+        // the failed read is left out, the empty device tag too
+        assert_eq!(std::str::from_utf8(&buf[..n]).unwrap(), "\
+sia_temperature,id=coretemp@platform/coretemp.0/temp1,group=CPU,sensor=CPU\\ (Package),chip=coretemp,device=platform/coretemp.0 value=45 1748772000000000000
+sia_utilization,id=cpu/util,group=util,sensor=CPU,chip=sysinfo value=12.346 1748772000000000000
+");
+        assert!(p.last_error().is_none());
+    }
+
+    #[test]
+    fn graphite_over_tcp_flushed_on_drop() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (channels, groups) = cpu();
+        let config = PushConfig { target: format!("tcp://{}", listener.local_addr().unwrap()).parse().unwrap(), format: LineFormat::Graphite, every: Duration::from_secs(3600) };
+        let sinks = SinkThread::spawn(|err| assert_eq!(err, None));
+        sinks.add(Box::new(Pusher::new(config, &channels, &groups).unwrap()));
+        let (conn, _) = listener.accept().unwrap();
+        sinks.send(&snap(1_748_772_000, vec![Some(45.0), Some(44.5), None]));
+        sinks.send(&snap(1_748_772_001, vec![Some(46.0), None, Some(3.0)]));
+        // batched for an hour, so nothing is sent until the sink thread is dropped
+        drop(sinks);
+        let lines: Vec<String> = BufReader::new(conn).lines().map(Result::unwrap).collect();
+        assert_eq!(lines, [
+            "sia.temperature.CPU.CPU__Package_.coretemp_platform_coretemp_0_temp1 45 1748772000",
+            "sia.temperature.CPU.CPU__Package_.coretemp_platform_coretemp_0_temp2 44.5 1748772000",
+            "sia.temperature.CPU.CPU__Package_.coretemp_platform_coretemp_0_temp1 46 1748772001",
+            "sia.utilization.util.CPU.cpu_util 3 1748772001",
+        ]);
+    }
+
+    #[test]
+    fn unreachable_target() {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let (channels, groups) = cpu();
+        let config = PushConfig { target: Target::Tcp(addr.to_string()), format: LineFormat::Influx, every: Duration::ZERO };
+        let e = Pusher::new(config, &channels, &groups).err().expect("nothing listens there");
+        assert!(e.to_string().starts_with(&format!("tcp://{}: ", addr)), "{}", e);
+        assert!("http://x".parse::<Target>().is_err());
+        assert_eq!("udp://[::1]:8089".parse::<Target>(), Ok(Target::Udp("[::1]:8089".into())));
+    }
+}
@@ -0,0 +1,316 @@
+//! Session recording. Every channel is written with its stable id, name, unit and group, one row per sample,
+//! in one of two formats picked by file extension:
+//!
+//! * CSV (`.csv`, the default): a header `unix_time,elapsed_s,<id>...`, then `#name`, `#unit` and `#group`
+//!   rows carrying the metadata per column, then one row per sample. A failed read is an empty cell.
+//! * JSON Lines (`.jsonl`, `.ndjson`, `.json`): a first line `{"sia":1,"channels":[...],"groups":[...]}` with
+//!   the metadata and sensor groups, then one `{"unix_time":..,"elapsed_s":..,"values":[..]}` per sample,
+//!   `null` for a failed read.
+//!
+//! `unix_time` is wall-clock seconds (to line up with logs); `elapsed_s` is CLOCK_BOOTTIME seconds since the
+//! first recorded sample, which neither jumps with NTP nor stops during suspend.
+//!
+//! [`Recording::load`] reads either format back for replay.
+use crate::collector::{Channel, Kind, Snapshot};
+use crate::rules::Rules;
+use crate::taxonomy::{palette, tint, Rgb, SensorGroup, SensorItem};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format { Csv, JsonLines }
+
+impl Format {
+    pub fn from_path(p: &Path) -> Self {
+        match p.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("jsonl" | "ndjson" | "json") => Format::JsonLines,
+            _ => Format::Csv,
+        }
+    }
+}
+
+/// Channel metadata as recorded. CSV has no `color`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChannelMeta {
+    pub id: String, pub name: String, pub unit: String, pub group: String, pub chip: String, pub device: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<Rgb>,
+}
+
+impl ChannelMeta {
+    pub fn of(ch: &Channel) -> Self {
+        Self { id: ch.id.clone(), name: ch.name.clone(), unit: ch.kind.unit().into(), group: ch.group.clone(), chip: ch.chip.clone(), device: ch.device.clone(), color: Some(ch.color) }
+    }
+    pub fn kind(&self) -> Kind {
+        match self.unit.as_str() { "%" => Kind::Utilization, "MHz" => Kind::Frequency, _ => Kind::Temperature }
+    }
+}
+
+/// `groups` is empty in files written before groups were recorded. `host` is the recording machine's name.
+#[derive(Serialize, Deserialize)]
+pub struct Header {
+    pub sia: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    pub channels: Vec<ChannelMeta>,
+    #[serde(default)]
+    pub groups: Vec<SensorGroup>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Row { pub unix_time: f64, pub elapsed_s: f64, pub values: Vec<Option<f64>> }
+
+pub struct Recorder {
+    out: BufWriter<Box<dyn Write + Send>>,
+    format: Format,
+    path: PathBuf,
+    boot0: Option<Duration>,
+    rows: u64,
+}
+
+impl Recorder {
+    /// Creates (truncates) `path` and writes the header for `channels`; JSON Lines also keeps `groups`.
+    pub fn create(path: &Path, channels: &[Channel], groups: &[SensorGroup]) -> io::Result<Self> {
+        let file = File::create(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        Self::to_writer(Box::new(file), Format::from_path(path), path, channels, groups)
+    }
+
+    /// Like [`Self::create`], but streams to `out` (a socket, stdout); `path` only names the stream.
+    pub fn to_writer(out: Box<dyn Write + Send>, format: Format, path: &Path, channels: &[Channel], groups: &[SensorGroup]) -> io::Result<Self> {
+        let mut out = BufWriter::new(out);
+        match format {
+            Format::Csv => {
+                let row = |first: &str, f: &dyn Fn(&Channel) -> String| {
+                    let mut cells = vec![first.to_string(), String::new()];
+                    cells.extend(channels.iter().map(|c| csv_field(&f(c))));
+                    cells.join(",")
+                };
+                let mut header = vec!["unix_time".to_string(), "elapsed_s".to_string()];
+                header.extend(channels.iter().map(|c| csv_field(&c.id)));
+                writeln!(out, "{}", header.join(","))?;
+                writeln!(out, "{}", row("#name", &|c| c.name.clone()))?;
+                writeln!(out, "{}", row("#unit", &|c| c.kind.unit().to_string()))?;
+                writeln!(out, "{}", row("#group", &|c| c.group.clone()))?;
+            }
+            Format::JsonLines => {
+                let header = Header { sia: 1, host: Some(crate::hostname()), channels: channels.iter().map(ChannelMeta::of).collect(), groups: groups.to_vec() };
+                writeln!(out, "{}", serde_json::to_string(&header)?)?;
+            }
+        }
+        out.flush()?;
+        Ok(Self { out, format, path: path.to_path_buf(), boot0: None, rows: 0 })
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+    pub fn rows(&self) -> u64 { self.rows }
+
+    /// Appends one sample and flushes, so a crash loses at most the sample being written.
+    pub fn write(&mut self, snap: &Snapshot) -> io::Result<()> {
+        let boot0 = *self.boot0.get_or_insert(snap.boot);
+        let unix_time = snap.wall.duration_since(UNIX_EPOCH).map(|d| d.as_secs_f64()).unwrap_or(0.0);
+        let elapsed_s = snap.boot.saturating_sub(boot0).as_secs_f64();
+        match self.format {
+            Format::Csv => {
+                let mut line = format!("{:.3},{:.3}", unix_time, elapsed_s);
+                for v in &snap.values { line.push(','); if let Some(v) = v { line.push_str(&format!("{}", round(*v))); } }
+                writeln!(self.out, "{}", line)?;
+            }
+            Format::JsonLines => {
+                let row = Row { unix_time: round(unix_time), elapsed_s: round(elapsed_s), values: snap.values.iter().map(|v| v.map(round)).collect() };
+                writeln!(self.out, "{}", serde_json::to_string(&row)?)?;
+            }
+        }
+        self.out.flush()?;
+        self.rows += 1;
+        Ok(())
+    }
+}
+
+/// A recording read back into memory.
+pub struct Recording {
+    pub path: PathBuf,
+    /// Machine it was recorded on, if the file says.
+    pub host: Option<String>,
+    pub channels: Vec<Channel>,
+    pub groups: Vec<SensorGroup>,
+    pub rows: Vec<Row>,
+}
+
+impl Recording {
+    /// Reads a file written by [`Recorder`], in either format. CSV carries no groups or colors; they are
+    /// rebuilt from each channel's group key with `rules`, as the live collector would name them.
+    pub fn load(path: &Path, rules: &Rules) -> io::Result<Self> {
+        let bad = |line: usize, msg: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: {}", path.display(), line, msg));
+        let file = File::open(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        let mut lines = BufReader::new(file).lines().enumerate().filter(|(_, l)| !matches!(l, Ok(l) if l.trim().is_empty()));
+        let Some((_, first)) = lines.next() else { return Err(bad(1, "empty file".into())) };
+        let first = first?;
+        let (host, metas, groups, rows) = if first.trim_start().starts_with('{') {
+            let header: Header = serde_json::from_str(&first).map_err(|e| bad(1, e.to_string()))?;
+            let mut rows = vec![];
+            for (n, l) in lines {
+                let row: Row = serde_json::from_str(&l?).map_err(|e| bad(n + 1, e.to_string()))?;
+                if row.values.len() != header.channels.len() { return Err(bad(n + 1, format!("{} values for {} channels", row.values.len(), header.channels.len()))); }
+                rows.push(row);
+            }
+            (header.host, header.channels, header.groups, rows)
+        } else {
+            let ids: Vec<String> = csv_split(&first).into_iter().skip(2).collect();
+            let mut meta: BTreeMap<String, Vec<String>> = BTreeMap::new();
+            let mut rows = vec![];
+            for (n, l) in lines {
+                let cells = csv_split(&l?);
+                if cells.len() != ids.len() + 2 { return Err(bad(n + 1, format!("{} columns, expected {}", cells.len(), ids.len() + 2))); }
+                if cells[0].starts_with('#') { meta.insert(cells[0].clone(), cells[2..].to_vec()); continue; }
+                let num = |c: &str| c.parse::<f64>().map_err(|_| bad(n + 1, format!("not a number: {:?}", c)));
+                let values = cells[2..].iter().map(|c| if c.is_empty() { Ok(None) } else { num(c).map(Some) }).collect::<io::Result<_>>()?;
+                rows.push(Row { unix_time: num(&cells[0])?, elapsed_s: num(&cells[1])?, values });
+            }
+            let col = |key: &str, i: usize| meta.get(key).map(|v| v[i].clone()).unwrap_or_default();
+            let metas = ids.iter().enumerate().map(|(i, id)| ChannelMeta {
+                id: id.clone(), name: col("#name", i), unit: col("#unit", i), group: col("#group", i), chip: String::new(), device: String::new(), color: None,
+            }).collect();
+            (None, metas, vec![], rows)
+        };
+        let (channels, groups) = resolve(&metas, groups, rules);
+        Ok(Self { path: path.to_path_buf(), host, channels, groups, rows })
+    }
+
+    /// `elapsed_s` of the last row.
+    pub fn duration(&self) -> f64 { self.rows.last().map(|r| r.elapsed_s).unwrap_or(0.0) }
+}
+
+/// Channels and groups for recorded metadata: groups are rebuilt when the header has none, and channels take
+/// their group item's color when the file has none.
+pub(crate) fn resolve(metas: &[ChannelMeta], groups: Vec<SensorGroup>, rules: &Rules) -> (Vec<Channel>, Vec<SensorGroup>) {
+    let groups = if groups.is_empty() { rebuild_groups(metas, rules) } else { groups };
+    let item_color = |i: usize| groups.iter().flat_map(|g| g.items.iter().chain(&g.freqs)).find(|it| it.idx == i).map(|it| it.color);
+    let pal = palette();
+    let channels = metas.iter().enumerate().map(|(i, m)| Channel {
+        id: m.id.clone(), name: m.name.clone(), kind: m.kind(), group: m.group.clone(), chip: m.chip.clone(), device: m.device.clone(),
+        color: m.color.or_else(|| item_color(i)).unwrap_or(pal[i % pal.len()]),
+    }).collect();
+    (channels, groups)
+}
+
+/// Groups for channels recorded without them: one per group key, named and colored from the rules by the
+/// key's kind (the part before `@`). The first temperature and every clock of a group start visible.
+fn rebuild_groups(metas: &[ChannelMeta], rules: &Rules) -> Vec<SensorGroup> {
+    let mut groups: Vec<SensorGroup> = vec![];
+    for (idx, m) in metas.iter().enumerate().filter(|(_, m)| m.group != "util" && !m.group.is_empty()) {
+        let gi = match groups.iter().position(|g| g.key == m.group) {
+            Some(gi) => gi,
+            None => {
+                let c = rules.group_info(m.group.split('@').next().unwrap_or(&m.group));
+                groups.push(SensorGroup { key: m.group.clone(), kind: c.group, display: c.display, items: vec![], freqs: vec![], visible: true, warn: c.warn, hot: c.hot, color: c.color, rank: c.rank, show_thresholds: false, socket: None });
+                groups.len() - 1
+            }
+        };
+        let g = &mut groups[gi];
+        let item = SensorItem { id: m.id.clone(), name: m.name.clone(), idx, visible: m.kind() == Kind::Frequency || g.items.is_empty(), color: Rgb::WHITE };
+        if m.kind() == Kind::Frequency { g.freqs.push(item) } else { g.items.push(item) }
+    }
+    let mut per_kind: BTreeMap<String, usize> = BTreeMap::new();
+    for g in &groups { *per_kind.entry(g.kind.clone()).or_default() += 1; }
+    let mut ordinal: BTreeMap<String, usize> = BTreeMap::new();
+    let pal = palette();
+    for g in &mut groups {
+        let n = ordinal.entry(g.kind.clone()).or_default();
+        if per_kind[&g.kind] > 1 { g.display = format!("{} #{}", g.display, *n + 1); }
+        let base = tint(g.color, (*n as f32) * 0.2);
+        for (i, it) in g.items.iter_mut().enumerate() { it.color = metas[it.idx].color.unwrap_or(tint(base, (i as f32) * 0.08)); }
+        for (i, it) in g.freqs.iter_mut().enumerate() { it.color = metas[it.idx].color.unwrap_or(pal[i % pal.len()]); }
+        *n += 1;
+    }
+    groups.sort_by_key(|g| g.rank);
+    groups
+}
+
+/// Splits one CSV line as written by [`csv_field`].
+fn csv_split(line: &str) -> Vec<String> {
+    let (mut cells, mut cur, mut quoted) = (vec![], String::new(), false);
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => { cur.push('"'); chars.next(); }
+            '"' => quoted = !quoted,
+            ',' if !quoted => cells.push(std::mem::take(&mut cur)),
+            _ => cur.push(c),
+        }
+    }
+    cells.push(cur);
+    cells
+}
+
+/// Three decimals is below any sensor's resolution and keeps files small.
+pub(crate) fn round(v: f64) -> f64 { (v * 1000.0).round() / 1000.0 }
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn channel(id: &str, name: &str, kind: Kind, group: &str) -> Channel {
+        Channel { id: id.into(), name: name.into(), kind, group: group.into(), chip: String::new(), device: String::new(), color: Rgb::WHITE }
+    }
+
+    fn snap(boot_s: u64, values: Vec<Option<f64>>) -> Snapshot {
+        Snapshot { mono: Instant::now(), boot: Duration::from_secs(boot_s), wall: UNIX_EPOCH + Duration::from_secs(1_700_000_000 + boot_s), raw: values.clone(), values }
+    }
+
+    fn round_trip(ext: &str) {
+        let channels = [
+            channel("cpu/util", "CPU", Kind::Utilization, "util"),
+            channel("nct6798@platform/nct6775.656/temp1", "SYSTIN, \"board\"", Kind::Temperature, "board@platform/nct6775.656"),
+            channel("cpu0/freq", "Core 0", Kind::Frequency, "cpu@platform/coretemp.0"),
+        ];
+        let path = std::env::temp_dir().join(format!("sia-record-{}.{}", std::process::id(), ext));
+        let mut r = Recorder::create(&path, &channels, &[]).unwrap();
+        r.write(&snap(100, vec![Some(12.5), Some(41.0), Some(3400.0)])).unwrap();
+        r.write(&snap(101, vec![Some(1.0 / 3.0), None, Some(800.0)])).unwrap();
+        assert_eq!(r.rows(), 2);
+        drop(r);
+        let rec = Recording::load(&path, &Rules::load(None)).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(rec.channels.iter().map(|c| (c.id.as_str(), c.name.as_str(), c.kind)).collect::<Vec<_>>(),
+            channels.iter().map(|c| (c.id.as_str(), c.name.as_str(), c.kind)).collect::<Vec<_>>());
+        assert_eq!(rec.channels[1].group, "board@platform/nct6775.656");
+        assert_eq!(rec.rows.len(), 2);
+        assert_eq!((rec.rows[0].unix_time, rec.rows[0].elapsed_s), (1_700_000_100.0, 0.0));
+        assert_eq!(rec.rows[1].values, [Some(0.333), None, Some(800.0)]);
+        assert_eq!(rec.duration(), 1.0);
+        // groups come from the header, or are rebuilt from the group keys
+        assert!(rec.groups.iter().any(|g| g.items.iter().any(|it| it.idx == 1)));
+        assert!(rec.groups.iter().any(|g| g.freqs.iter().any(|it| it.idx == 2)));
+    }
+
+    #[test]
+    fn csv_round_trip() { round_trip("csv"); }
+
+    #[test]
+    fn jsonl_round_trip() { round_trip("jsonl"); }
+
+    #[test]
+    fn csv_quoting() {
+        for s in ["plain", "a,b", "say \"hi\"", "\"", ""] {
+            assert_eq!(csv_split(&format!("{},{}", csv_field(s), csv_field("x"))), [s, "x"]);
+        }
+    }
+
+    #[test]
+    fn bad_rows() {
+        let path = std::env::temp_dir().join(format!("sia-record-bad-{}.csv", std::process::id()));
+        std::fs::write(&path, "unix_time,elapsed_s,cpu/util\n1.0,0.0\n").unwrap();
+        let err = Recording::load(&path, &Rules::load(None)).err().map(|e| e.to_string());
+        let _ = std::fs::remove_file(&path);
+        assert!(err.is_some_and(|e| e.ends_with(":2: 2 columns, expected 3")));
+    }
+}
@@ -0,0 +1,340 @@
+//! Viewer side of `sia agent`. An agent streams a JSON Lines recording (see [`crate::record`]): the header with
+//! channels and groups, then one row per sample. A [`Remote`] reads it over TCP, or from
+//! `ssh HOST sia agent --stdio`, into a [`History`] like a local sampler's, and reconnects when the stream
+//! drops or goes quiet for [`STALE_PERIODS`] of the agent's sample periods.
+use crate::collector::Channel;
+use crate::push::{connect_tcp, NET_TIMEOUT};
+use crate::record::{self, Header, Row};
+use crate::rules::Rules;
+use crate::sampler::History;
+use crate::taxonomy::SensorGroup;
+use std::io::{self, BufRead, BufReader, Read};
+use std::net::TcpStream;
+use std::process::{Child, Command, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Port `sia agent` listens on unless told otherwise.
+pub const DEFAULT_PORT: u16 = 7979;
+
+/// How long to wait before reconnecting to an agent that dropped.
+const RETRY: Duration = Duration::from_secs(5);
+
+/// Sample periods without a row after which an agent counts as offline and the stream is rebuilt.
+pub const STALE_PERIODS: u32 = 5;
+
+/// How long a row may take before the agent's period is known, and how long ssh may take to log in.
+const SLOW_START: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Endpoint { Tcp(String), Ssh(String) }
+
+impl FromStr for Endpoint {
+    type Err = String;
+    /// `HOST[:PORT]`, `tcp://HOST[:PORT]` or `ssh://[USER@]HOST`.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let with_port = |a: &str| if a.rsplit_once(':').is_some_and(|(_, p)| p.parse::<u16>().is_ok()) { a.to_string() } else { format!("{}:{}", a, DEFAULT_PORT) };
+        match s.split_once("://") {
+            Some(("ssh", h)) if !h.is_empty() => Ok(Endpoint::Ssh(h.into())),
+            Some(("tcp", a)) if !a.is_empty() => Ok(Endpoint::Tcp(with_port(a))),
+            None if !s.is_empty() => Ok(Endpoint::Tcp(with_port(s))),
+            _ => Err(format!("{}: expected HOST[:PORT], tcp://HOST[:PORT] or ssh://[USER@]HOST", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self { Endpoint::Tcp(a) => write!(f, "tcp://{}", a), Endpoint::Ssh(h) => write!(f, "ssh://{}", h) }
+    }
+}
+
+/// The ssh child and when it last sent a row, shared with the watchdog that kills it once it has been
+/// silent for `limit`: a pipe has no read timeout.
+struct Watch { child: Option<Child>, last_row: Instant, limit: Duration, fired: bool }
+
+/// An open stream; the ssh child is killed with it. A TCP stream times out by itself.
+struct Stream { lines: io::Lines<BufReader<Box<dyn Read + Send>>>, tcp: Option<TcpStream>, ssh: Option<Arc<Mutex<Watch>>> }
+
+impl Stream {
+    /// Rows may be at most `limit` apart from now on.
+    fn stale_after(&self, limit: Duration) -> io::Result<()> {
+        if let Some(t) = &self.tcp { t.set_read_timeout(Some(limit))?; }
+        if let Some(w) = &self.ssh { w.lock().unwrap_or_else(|e| e.into_inner()).limit = limit; }
+        Ok(())
+    }
+
+    fn got_row(&self) {
+        if let Some(w) = &self.ssh { w.lock().unwrap_or_else(|e| e.into_inner()).last_row = Instant::now(); }
+    }
+
+    /// Why the stream failed, if it was for going quiet.
+    fn stalled(&self, e: Option<&io::Error>) -> Option<Duration> {
+        match (&self.tcp, &self.ssh) {
+            (Some(t), _) if e.is_some_and(|e| matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut)) => t.read_timeout().ok().flatten(),
+            (_, Some(w)) => { let w = w.lock().unwrap_or_else(|e| e.into_inner()); w.fired.then_some(w.limit) }
+            _ => None,
+        }
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        let child = self.ssh.as_ref().and_then(|w| w.lock().unwrap_or_else(|e| e.into_inner()).child.take());
+        if let Some(mut c) = child { let _ = c.kill(); let _ = c.wait(); }
+    }
+}
+
+fn watchdog(watch: Arc<Mutex<Watch>>) -> io::Result<()> {
+    thread::Builder::new().name("sia-ssh-watchdog".into()).spawn(move || loop {
+        thread::sleep(Duration::from_millis(250));
+        let mut w = watch.lock().unwrap_or_else(|e| e.into_inner());
+        let (elapsed, limit) = (w.last_row.elapsed(), w.limit);
+        let Some(c) = w.child.as_mut() else { return };
+        if elapsed > limit { let _ = c.kill(); w.fired = true; return; }
+    })?;
+    Ok(())
+}
+
+fn open(ep: &Endpoint) -> io::Result<(Stream, Header)> {
+    let with_ep = |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", ep, e));
+    let mut s = match ep {
+        Endpoint::Tcp(a) => {
+            let t = connect_tcp(a).map_err(with_ep)?;
+            t.set_read_timeout(Some(NET_TIMEOUT)).map_err(with_ep)?;
+            Stream { lines: BufReader::new(Box::new(t.try_clone().map_err(with_ep)?) as Box<dyn Read + Send>).lines(), tcp: Some(t), ssh: None }
+        }
+        Endpoint::Ssh(h) => {
+            let mut c = Command::new("ssh").args(["-T", "-o", "BatchMode=yes", h, "sia", "agent", "--stdio"])
+                .stdin(Stdio::null()).stdout(Stdio::piped()).spawn().map_err(with_ep)?;
+            let out: Box<dyn Read + Send> = Box::new(c.stdout.take().expect("piped stdout"));
+            let watch = Arc::new(Mutex::new(Watch { child: Some(c), last_row: Instant::now(), limit: SLOW_START, fired: false }));
+            watchdog(watch.clone()).map_err(with_ep)?;
+            Stream { lines: BufReader::new(out).lines(), tcp: None, ssh: Some(watch) }
+        }
+    };
+    let line = s.lines.next().unwrap_or_else(|| Err(io::Error::new(io::ErrorKind::UnexpectedEof, "closed before sending its sensors"))).map_err(with_ep)?;
+    let header: Header = serde_json::from_str(&line).map_err(|e| with_ep(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+    Ok((s, header))
+}
+
+/// What an agent says about itself at the start of its stream.
+#[derive(Clone)]
+pub struct Agent { pub host: String, pub channels: Vec<Channel>, pub groups: Vec<SensorGroup> }
+
+pub struct Remote {
+    endpoint: Endpoint,
+    agent: Arc<Mutex<Option<Agent>>>,
+    history: Arc<Mutex<History>>,
+    status: Arc<Mutex<Option<String>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Remote {
+    /// Starts disconnected: a background thread connects, reads the agent's sensors and then its samples, and
+    /// keeps retrying while the agent is unreachable. Until the first connection [`Remote::agent`] is `None`
+    /// and [`Remote::status`] says why. Keeps `capacity` samples per channel.
+    pub fn spawn(endpoint: Endpoint, capacity: usize, rules: Rules) -> Self {
+        let agent = Arc::new(Mutex::new(None));
+        let history = Arc::new(Mutex::new(History::new(0, capacity, 1.0)));
+        let status = Arc::new(Mutex::new(Some(format!("{}: connecting", endpoint))));
+        let stop = Arc::new(AtomicBool::new(false));
+        {
+            let (endpoint, agent, history, status, stop) = (endpoint.clone(), agent.clone(), history.clone(), status.clone(), stop.clone());
+            thread::Builder::new().name("sia-remote".into()).spawn(move || {
+                let set_status = |s: Option<String>| {
+                    *status.lock().unwrap_or_else(|e| e.into_inner()) = s;
+                    history.lock().unwrap_or_else(|e| e.into_inner()).wake();
+                };
+                // the agent's channel ids, fixed by the first connection
+                let mut ids: Option<Vec<String>> = None;
+                // x continues across reconnects: each connection's elapsed_s is offset to line up by wall clock
+                let mut unix0: Option<f64> = None;
+                // the agent's period, once two rows have shown it
+                let mut period: Option<f64> = None;
+                let stale = |period: Option<f64>| period.map(|p| Duration::from_secs_f64(p * STALE_PERIODS as f64).max(NET_TIMEOUT)).unwrap_or(SLOW_START);
+                let mut first = true;
+                while !stop.load(Ordering::Relaxed) {
+                    if !std::mem::take(&mut first) {
+                        thread::sleep(RETRY);
+                        if stop.load(Ordering::Relaxed) { return; }
+                    }
+                    let (mut s, header) = match open(&endpoint) {
+                        Ok(v) => v,
+                        Err(e) => { set_status(Some(format!("{}; retrying", e))); continue; }
+                    };
+                    let n = header.channels.len();
+                    match &ids {
+                        Some(ids) if !header.channels.iter().map(|c| &c.id).eq(ids.iter()) => {
+                            set_status(Some(format!("{}: sensors changed on the agent; reconnect to see them", endpoint)));
+                            return;
+                        }
+                        Some(_) => {}
+                        None => {
+                            ids = Some(header.channels.iter().map(|c| c.id.clone()).collect());
+                            let (channels, groups) = record::resolve(&header.channels, header.groups, &rules);
+                            {
+                                let mut h = history.lock().unwrap_or_else(|e| e.into_inner());
+                                let on_sample = h.on_sample.take();
+                                *h = History::new(n, capacity, 1.0);
+                                h.on_sample = on_sample;
+                            }
+                            let host = header.host.unwrap_or_else(|| endpoint.to_string());
+                            *agent.lock().unwrap_or_else(|e| e.into_inner()) = Some(Agent { host, channels, groups });
+                        }
+                    }
+                    set_status(None);
+                    let mut base: Option<f64> = None;
+                    let mut steps: Vec<f64> = vec![];
+                    let mut prev_x: Option<f64> = None;
+                    let err = loop {
+                        if stop.load(Ordering::Relaxed) { return; }
+                        if let Err(e) = s.stale_after(stale(period)) { break e.to_string(); }
+                        let line = s.lines.next();
+                        if let Some(quiet) = s.stalled(line.as_ref().and_then(|l| l.as_ref().err())) {
+                            break format!("no samples for {:.0} s, agent offline", quiet.as_secs_f64());
+                        }
+                        let row: Row = match line {
+                            Some(Ok(l)) => match serde_json::from_str(&l) { Ok(r) => r, Err(e) => break e.to_string() },
+                            Some(Err(e)) => break e.to_string(),
+                            None => break "agent closed the connection".into(),
+                        };
+                        s.got_row();
+                        if row.values.len() != n { break format!("{} values for {} channels", row.values.len(), n); }
+                        let u0 = *unix0.get_or_insert(row.unix_time);
+                        let x = row.elapsed_s + *base.get_or_insert(row.unix_time - u0 - row.elapsed_s);
+                        let mut h = history.lock().unwrap_or_else(|e| e.into_inner());
+                        // after a reconnect the agent's backlog repeats rows we already have
+                        if h.samples > 0 && x <= h.now { continue; }
+                        // the agent's period, as the median of the first steps
+                        if let Some(p) = prev_x.filter(|p| x > *p && steps.len() < 9) {
+                            steps.push(x - p);
+                            let mut sorted = steps.clone();
+                            sorted.sort_by(f64::total_cmp);
+                            h.period = sorted[sorted.len() / 2];
+                            period = Some(h.period);
+                        }
+                        prev_x = Some(x);
+                        h.push_row(x, &row);
+                    };
+                    set_status(Some(format!("{}: {}; reconnecting", endpoint, err)));
+                }
+            }).expect("spawning remote thread");
+        }
+        Self { endpoint, agent, history, status, stop }
+    }
+
+    pub fn endpoint(&self) -> &Endpoint { &self.endpoint }
+    /// The agent's machine name and sensors, once it has been reached.
+    pub fn agent(&self) -> Option<Agent> { self.agent.lock().unwrap_or_else(|e| e.into_inner()).clone() }
+    pub fn history(&self) -> Arc<Mutex<History>> { self.history.clone() }
+    /// Why the stream is down, `None` while samples arrive.
+    pub fn status(&self) -> Option<String> { self.status.lock().unwrap_or_else(|e| e.into_inner()).clone() }
+}
+
+impl Drop for Remote {
+    /// The reader may be blocked on the stream, so it is not joined; it exits at the next row or reconnect.
+    fn drop(&mut self) { self.stop.store(true, Ordering::Relaxed); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collector::{Kind, Snapshot};
+    use crate::record::{Format, Recorder};
+    use crate::taxonomy::Rgb;
+    use std::net::TcpListener;
+    use std::path::Path;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn snap(k: u64, v: f64) -> Snapshot {
+        let t = Duration::from_millis(100 * k);
+        Snapshot { mono: std::time::Instant::now(), boot: t, wall: UNIX_EPOCH + Duration::from_secs(1_700_000_000) + t, values: vec![Some(v), None], raw: vec![] }
+    }
+
+    fn wait_for(r: &Remote, what: impl Fn(&Remote) -> bool) {
+        let deadline = Instant::now() + RETRY + NET_TIMEOUT * 2;
+        while !what(r) { assert!(Instant::now() < deadline, "status {:?}", r.status()); thread::sleep(Duration::from_millis(20)); }
+    }
+
+    #[test]
+    fn tcp_round_trip() {
+        let channels = vec![
+            Channel { id: "coretemp@platform/coretemp.0/temp1".into(), name: "CPU (Package)".into(), kind: Kind::Temperature, group: "cpu@platform/coretemp.0".into(), chip: "coretemp".into(), device: "platform/coretemp.0".into(), color: Rgb::WHITE },
+            Channel { id: "cpu/util".into(), name: "CPU".into(), kind: Kind::Utilization, group: "util".into(), chip: "sysinfo".into(), device: String::new(), color: Rgb::WHITE },
+        ];
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let ep = Endpoint::Tcp(listener.local_addr().unwrap().to_string());
+        let agent = {
+            let channels = channels.clone();
+            thread::spawn(move || {
+                let viewer = |n: u64| {
+                    let (s, _) = listener.accept().unwrap();
+                    let mut r = Recorder::to_writer(Box::new(s), Format::JsonLines, Path::new("viewer"), &channels, &[]).unwrap();
+                    // a reconnecting viewer first gets the backlog again
+                    for k in n.saturating_sub(2)..n + 3 { r.write(&snap(k, 40.0 + k as f64)).unwrap(); }
+                    r
+                };
+                // the first connection goes quiet, the second one is closed after its rows
+                let quiet = viewer(0);
+                drop(viewer(3));
+                quiet
+            })
+        };
+
+        let r = Remote::spawn(ep, 100, Rules::load(None));
+        wait_for(&r, |r| r.history().lock().unwrap().samples == 3);
+        let a = r.agent().unwrap();
+        assert_eq!(a.host, crate::hostname());
+        assert_eq!(a.channels.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), ["coretemp@platform/coretemp.0/temp1", "cpu/util"]);
+        assert_eq!(a.channels[0].group, "cpu@platform/coretemp.0");
+        assert!(a.groups.iter().any(|g| g.items.iter().any(|it| it.idx == 0)));
+        assert_eq!(r.status(), None);
+
+        let started = SystemTime::now();
+        wait_for(&r, |r| r.status().is_some());
+        assert!(r.status().unwrap().contains("no samples for 5 s, agent offline"), "{:?}", r.status());
+        assert!(started.elapsed().unwrap() >= NET_TIMEOUT - Duration::from_millis(500));
+
+        // the repeated backlog is skipped and x carries on by wall clock
+        wait_for(&r, |r| r.history().lock().unwrap().samples == 6);
+        {
+            let h = r.history();
+            let h = h.lock().unwrap();
+            assert!((h.now - 0.5).abs() < 1e-3, "{}", h.now);
+            assert_eq!(h.series(0).last_y(), Some(45.0));
+            assert!((h.period - 0.1).abs() < 1e-3);
+        }
+        wait_for(&r, |r| r.status().is_some_and(|s| s.contains("agent closed the connection")));
+        drop(agent.join().unwrap());
+    }
+
+    #[test]
+    fn unreachable_agent() {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let r = Remote::spawn(Endpoint::Tcp(addr.to_string()), 100, Rules::load(None));
+        wait_for(&r, |r| r.status().is_some_and(|s| s.ends_with("; retrying")));
+        assert!(r.status().unwrap().starts_with(&format!("tcp://{}: ", addr)));
+        assert!(r.agent().is_none());
+        assert_eq!(r.history().lock().unwrap().samples, 0);
+    }
+
+    #[test]
+    fn watchdog_kills_a_quiet_child() {
+        let mut c = Command::new("sh").args(["-c", "echo '{}'; exec sleep 30"]).stdout(Stdio::piped()).spawn().unwrap();
+        let out: Box<dyn Read + Send> = Box::new(c.stdout.take().unwrap());
+        let watch = Arc::new(Mutex::new(Watch { child: Some(c), last_row: Instant::now(), limit: Duration::from_millis(300), fired: false }));
+        watchdog(watch.clone()).unwrap();
+        let mut s = Stream { lines: BufReader::new(out).lines(), tcp: None, ssh: Some(watch) };
+        assert_eq!(s.lines.next().unwrap().unwrap(), "{}");
+        s.got_row();
+        assert_eq!(s.stalled(None), None);
+        let started = Instant::now();
+        assert!(s.lines.next().is_none());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(s.stalled(None), Some(Duration::from_millis(300)));
+    }
+}
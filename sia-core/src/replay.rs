@@ -0,0 +1,81 @@
+//! Playback of a [`Recording`]: a [`Player`] feeds the recorded rows into a [`History`] as its cursor moves,
+//! so anything that draws a live sampler's history can show a recording unchanged.
+use crate::record::Recording;
+use crate::sampler::History;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+pub struct Player {
+    rec: Recording,
+    history: Arc<Mutex<History>>,
+    capacity: usize,
+    period: f64,
+    /// Index of the first row not yet in the history.
+    next: usize,
+    /// Playback position, in the recording's `elapsed_s`.
+    cursor: f64,
+    pub playing: bool,
+    /// Recorded seconds per second of playback.
+    pub speed: f64,
+}
+
+impl Player {
+    /// Starts paused at the beginning, keeping the last `capacity` rows and aggregates of older ones in the
+    /// history like a sampler would.
+    pub fn new(rec: Recording, capacity: usize) -> Self {
+        // the recording's own sampling period, so gaps in it show as holes
+        let mut steps: Vec<f64> = rec.rows.windows(2).map(|w| w[1].elapsed_s - w[0].elapsed_s).filter(|d| *d > 0.0).collect();
+        steps.sort_by(f64::total_cmp);
+        let period = steps.get(steps.len() / 2).copied().unwrap_or(1.0);
+        let history = Arc::new(Mutex::new(History::new(rec.channels.len(), capacity, period)));
+        let mut p = Self { rec, history, capacity, period, next: 0, cursor: 0.0, playing: false, speed: 1.0 };
+        p.seek(0.0);
+        p
+    }
+
+    pub fn recording(&self) -> &Recording { &self.rec }
+    pub fn history(&self) -> Arc<Mutex<History>> { self.history.clone() }
+    pub fn lock(&self) -> MutexGuard<'_, History> { self.history.lock().unwrap_or_else(|e| e.into_inner()) }
+    pub fn cursor(&self) -> f64 { self.cursor }
+    pub fn duration(&self) -> f64 { self.rec.duration() }
+
+    /// Moves the cursor by `dt` seconds of wall time at the current speed, if playing; stops at the end.
+    pub fn advance(&mut self, dt: Duration) {
+        if !self.playing { return; }
+        self.cursor = (self.cursor + dt.as_secs_f64() * self.speed).min(self.duration());
+        if self.cursor >= self.duration() { self.playing = false; }
+        self.feed();
+    }
+
+    /// Jumps to `t` seconds into the recording. A short step forward feeds the rows in between as playback
+    /// would; otherwise the history is rebuilt from the rows up to `t`.
+    pub fn seek(&mut self, t: f64) {
+        let t = t.clamp(0.0, self.duration());
+        let span = self.lock().span();
+        if self.next > 0 && t >= self.cursor && t - self.cursor < span { self.cursor = t; return self.feed(); }
+        self.cursor = t;
+        let mut h = History::new(self.rec.channels.len(), self.capacity, self.period);
+        let from = self.cursor - h.span();
+        self.next = self.rec.rows.partition_point(|r| r.elapsed_s < from);
+        h.samples = self.next as u64;
+        {
+            // keep waking whoever was woken by new rows
+            let mut old = self.lock();
+            h.on_sample = old.on_sample.take();
+            *old = h;
+        }
+        self.feed();
+    }
+
+    fn feed(&mut self) {
+        let mut h = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        // one wake for the lot, not one per row
+        let (on_sample, from) = (h.on_sample.take(), self.next);
+        while let Some(row) = self.rec.rows.get(self.next).filter(|r| r.elapsed_s <= self.cursor) {
+            h.push_row(row.elapsed_s, row);
+            self.next += 1;
+        }
+        h.on_sample = on_sample;
+        if self.next > from { h.wake(); }
+    }
+}
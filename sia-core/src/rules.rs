@@ -0,0 +1,242 @@
+//! Classification rules. The taxonomy (group, title, thresholds, color, label rewrite, order) is data:
+//! a bundled table (`rules.toml`) with an optional user table in front of it. See `rules.toml` for the format.
+use crate::taxonomy::Rgb;
+use regex::Regex;
+use serde::Deserialize;
+use std::path::Path;
+use std::{fs, io};
+
+const BUNDLED: &str = include_str!("rules.toml");
+
+#[derive(Debug, thiserror::Error)]
+pub enum RuleError {
+    #[error("reading {0}: {1}")]
+    Io(String, io::Error),
+    #[error("parsing rules: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("bad regex in rule {0}: {1}")]
+    Regex(usize, regex::Error),
+    #[error("bad color {1:?} in rule {0}, expected \"#rrggbb\"")]
+    Color(usize, String),
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    driver: Option<String>,
+    label: Option<String>,
+    device: Option<String>,
+    group: Option<String>,
+    display: Option<String>,
+    warn: Option<f64>,
+    hot: Option<f64>,
+    color: Option<String>,
+    rank: Option<i32>,
+    rename: Option<String>,
+    order: Option<i32>,
+    show: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile { #[serde(default)] rule: Vec<RuleSpec> }
+
+#[derive(Debug)]
+struct Rule {
+    driver: Option<Regex>,
+    label: Option<Regex>,
+    device: Option<Regex>,
+    group: Option<String>,
+    display: Option<String>,
+    warn: Option<f64>,
+    hot: Option<f64>,
+    color: Option<Rgb>,
+    rank: Option<i32>,
+    rename: Option<String>,
+    order: Option<i32>,
+    show: Option<bool>,
+}
+
+/// What the rules say about one sensor. Group fields (`display` .. `rank`) come from the sensor's own
+/// rule matches; `build_groups` takes them from the first sensor of each group.
+#[derive(Clone, Debug)]
+pub struct Classified {
+    pub group: String,
+    pub display: String,
+    pub warn: f64,
+    pub hot: f64,
+    pub color: Rgb,
+    pub rank: i32,
+    pub label: String,
+    pub order: i32,
+    pub show: bool,
+}
+
+pub struct Rules { rules: Vec<Rule> }
+
+impl Rules {
+    /// User rules (if any) followed by the bundled table. A broken user file is reported and skipped.
+    pub fn load(user_file: Option<&Path>) -> Self {
+        let mut rules = vec![];
+        if let Some(path) = user_file.filter(|p| p.exists()) {
+            match fs::read_to_string(path).map_err(|e| RuleError::Io(path.display().to_string(), e)).and_then(|s| parse(&s)) {
+                Ok(mut r) => rules.append(&mut r),
+                Err(e) => eprintln!("sia: ignoring {}: {}", path.display(), e),
+            }
+        }
+        rules.extend(parse(BUNDLED).expect("bundled rules.toml is valid"));
+        Self { rules }
+    }
+
+    pub fn classify(&self, driver: &str, label: &str, device: &str) -> Classified {
+        let (mut group, mut display, mut warn, mut hot, mut color, mut rank, mut rename, mut order, mut show) = (None, None, None, None, None, None, None, None, None);
+        for r in self.rules.iter().filter(|r| r.matches(driver, label, device)) {
+            group = group.or_else(|| r.group.clone());
+            display = display.or_else(|| r.display.clone());
+            warn = warn.or(r.warn);
+            hot = hot.or(r.hot);
+            color = color.or(r.color);
+            rank = rank.or(r.rank);
+            rename = rename.or_else(|| r.rename.as_ref().map(|to| match &r.label { Some(re) => re.replace(label, to.as_str()).into_owned(), None => to.clone() }));
+            order = order.or(r.order);
+            show = show.or(r.show);
+        }
+        let group = group.unwrap_or_else(|| driver.to_string());
+        Classified {
+            display: display.unwrap_or_else(|| group.clone()),
+            warn: warn.unwrap_or(90.0),
+            hot: hot.unwrap_or(100.0),
+            color: color.unwrap_or_else(|| self.group_color(&group)),
+            rank: rank.unwrap_or(6),
+            label: rename.unwrap_or_else(|| label.to_string()),
+            order: order.unwrap_or(100),
+            show: show.unwrap_or(false),
+            group,
+        }
+    }
+
+    /// Group-level fields (`display` .. `rank`) the rules give `group` itself, for when only the group name
+    /// is known, e.g. a replayed recording. Sensor-level fields keep their defaults.
+    pub fn group_info(&self, group: &str) -> Classified {
+        let of_group = || self.rules.iter().filter(|r| r.group.as_deref() == Some(group));
+        Classified {
+            group: group.to_string(),
+            display: of_group().find_map(|r| r.display.clone()).unwrap_or_else(|| group.to_string()),
+            warn: of_group().find_map(|r| r.warn).unwrap_or(90.0),
+            hot: of_group().find_map(|r| r.hot).unwrap_or(100.0),
+            color: self.group_color(group),
+            rank: of_group().find_map(|r| r.rank).unwrap_or(6),
+            label: String::new(),
+            order: 100,
+            show: false,
+        }
+    }
+
+    /// Color of the first rule that assigns `group` a color; grey when none does.
+    pub fn group_color(&self, group: &str) -> Rgb {
+        self.rules.iter().find(|r| r.group.as_deref() == Some(group) && r.color.is_some()).and_then(|r| r.color).unwrap_or(Rgb(158,158,158))
+    }
+}
+
+impl Rule {
+    fn matches(&self, driver: &str, label: &str, device: &str) -> bool {
+        let m = |re: &Option<Regex>, s: &str| re.as_ref().map(|re| re.is_match(s)).unwrap_or(true);
+        m(&self.driver, driver) && m(&self.label, label) && m(&self.device, device)
+    }
+}
+
+fn parse(src: &str) -> Result<Vec<Rule>, RuleError> {
+    let file: RuleFile = toml::from_str(src)?;
+    file.rule.into_iter().enumerate().map(|(i, s)| {
+        let re = |p: Option<String>| p.map(|p| Regex::new(&p)).transpose().map_err(|e| RuleError::Regex(i + 1, e));
+        let color = match s.color { Some(c) => Some(parse_color(&c).ok_or(RuleError::Color(i + 1, c))?), None => None };
+        Ok(Rule {
+            driver: re(s.driver)?, label: re(s.label)?, device: re(s.device)?,
+            group: s.group, display: s.display, warn: s.warn, hot: s.hot, color, rank: s.rank,
+            rename: s.rename, order: s.order, show: s.show,
+        })
+    }).collect()
+}
+
+fn parse_color(s: &str) -> Option<Rgb> {
+    let hex = s.strip_prefix('#')?;
+    if hex.len() != 6 { return None; }
+    let v = u32::from_str_radix(hex, 16).ok()?;
+    Some(Rgb((v >> 16) as u8, (v >> 8) as u8, v as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_user(src: &str) -> Rules {
+        let mut rules = parse(src).unwrap();
+        rules.extend(parse(BUNDLED).unwrap());
+        Rules { rules }
+    }
+
+    #[test]
+    fn bundled_table() {
+        let r = Rules::load(None);
+        let c = r.classify("coretemp", "Package id 0", "platform/coretemp.0");
+        assert_eq!((c.group.as_str(), c.display.as_str(), c.label.as_str()), ("cpu", "CPU", "CPU (Package)"));
+        assert_eq!((c.warn, c.hot, c.color, c.rank, c.order, c.show), (90.0, 100.0, Rgb(0xf4, 0x43, 0x36), 0, 0, true));
+        let c = r.classify("nvme", "Composite", "");
+        assert_eq!((c.group.as_str(), c.label.as_str(), c.warn, c.show), ("nvme", "SSD", 70.0, true));
+        let c = r.classify("e1000e", "e1000e", "");
+        assert_eq!((c.display.as_str(), c.color, c.rank), ("Ethernet Controller (e1000)", Rgb(0xab, 0x47, 0xbc), 5));
+        assert_eq!(r.group_info("ramspd").display, "Memory (SPD Hub)");
+        assert_eq!(r.group_color("nope"), Rgb(158, 158, 158));
+    }
+
+    #[test]
+    fn unmatched_defaults() {
+        let c = Rules::load(None).classify("foo", "bar", "");
+        assert_eq!((c.group.as_str(), c.display.as_str(), c.label.as_str()), ("foo", "foo", "bar"));
+        assert_eq!((c.warn, c.hot, c.rank, c.order, c.show), (90.0, 100.0, 6, 100, false));
+    }
+
+    #[test]
+    fn first_match_wins_per_field() {
+        let r = with_user("[[rule]]\ndriver = \"coretemp\"\nwarn = 70.0\n\n[[rule]]\ndriver = \"coretemp\"\nwarn = 75.0\nhot = 80.0\n");
+        let c = r.classify("coretemp", "Core 0", "");
+        // warn from the first user rule, hot from the second, the rest from the bundled table
+        assert_eq!((c.warn, c.hot, c.display.as_str(), c.rank), (70.0, 80.0, "CPU", 0));
+        // a user rule that does not match leaves the bundled one in charge
+        assert_eq!(r.classify("k10temp", "Tctl", "").warn, 90.0);
+    }
+
+    #[test]
+    fn user_file_before_bundled() {
+        let path = std::env::temp_dir().join(format!("sia-rules-{}.toml", std::process::id()));
+        fs::write(&path, "[[rule]]\ndriver = \"nvme\"\ngroup = \"disk\"\ndisplay = \"Disks\"\nlabel = \"Composite\"\nrename = \"Drive\"\n").unwrap();
+        let c = Rules::load(Some(&path)).classify("nvme", "Composite", "");
+        assert_eq!((c.group.as_str(), c.display.as_str(), c.label.as_str(), c.warn), ("disk", "Disks", "Drive", 70.0));
+        // a broken user file is skipped, not fatal
+        fs::write(&path, "[[rule]]\ncolour = \"#000000\"\n").unwrap();
+        assert_eq!(Rules::load(Some(&path)).classify("nvme", "Composite", "").group, "nvme");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rename_captures() {
+        let r = Rules::load(None);
+        assert_eq!(r.classify("k10temp", "Core 12", "").label, "CPU (Core 12)");
+        let r = with_user("[[rule]]\nlabel = \"temp(\\\\d)\"\nrename = \"T$1\"\n\n[[rule]]\ndriver = \"acpitz\"\nrename = \"Board\"\n");
+        // only the matched part of the label is replaced
+        assert_eq!(r.classify("x", "my temp2 sensor", "").label, "my T2 sensor");
+        // without a label regex the whole label is
+        assert_eq!(r.classify("acpitz", "acpitz", "").label, "Board");
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(parse("[[rule]]\nlabel = \"(\"\n"), Err(RuleError::Regex(1, _))));
+        assert!(matches!(parse("[[rule]]\ndriver = \"a\"\n[[rule]]\ndevice = \"[\"\n"), Err(RuleError::Regex(2, _))));
+        assert!(matches!(parse("[[rule]]\ncolor = \"red\"\n"), Err(RuleError::Color(1, c)) if c == "red"));
+        assert!(matches!(parse("[[rule]]\ncolor = \"#12345\"\n"), Err(RuleError::Color(1, _))));
+        assert!(matches!(parse("[[rule]]\ncolour = \"#123456\"\n"), Err(RuleError::Toml(_))));
+        assert_eq!(parse_color("#00ff80"), Some(Rgb(0, 255, 128)));
+        assert_eq!(parse_color("00ff80"), None);
+    }
+}
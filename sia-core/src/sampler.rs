@@ -0,0 +1,238 @@
+//! Background sampling: a [`Sampler`] runs a [`Collector`] on its own thread at a fixed rate and keeps the
+//! readings in a shared [`History`], so consumers (the GUI) only read and never wait on sysfs.
+use crate::calibration::Calibration;
+use crate::collector::{Channel, Collector};
+use crate::metrics::Exporter;
+use crate::mqtt::{MqttConfig, Publisher};
+use crate::push::{PushConfig, Pusher, Sink, SinkThread};
+use crate::record::{Recorder, Row};
+use crate::series::{SampleStore, Series, TIERED_SPAN};
+use crate::taxonomy::SensorGroup;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A suspend detected between two samples: `x` is the first sample after resume, `secs` how long the
+/// machine was asleep.
+#[derive(Clone, Copy, Debug)]
+pub struct Suspend { pub x: f64, pub secs: f64 }
+
+/// Everything sampled so far. `series(i)` reads channel `i`; x is seconds since the sampler started on
+/// CLOCK_BOOTTIME, taken from each sample's own timestamp, so time spent suspended is a hole in every series
+/// rather than pre- and post-suspend samples joined together.
+pub struct History {
+    pub store: SampleStore,
+    /// Last reading of each channel as the sensor reported it (see [`crate::Snapshot::raw`]).
+    pub raw: Vec<Option<f64>>,
+    pub samples: u64,
+    /// Samples that started more than half a period after their scheduled tick.
+    pub late: u64,
+    /// x of the latest sample.
+    pub now: f64,
+    /// Sampling period in seconds.
+    pub period: f64,
+    /// Wall-clock time at x = 0. Re-anchored on every sample, so the clock axis follows NTP adjustments.
+    pub wall_at_zero: SystemTime,
+    /// Suspends within the retained history, oldest first.
+    pub suspends: Vec<Suspend>,
+    /// File being recorded to and rows written so far.
+    pub recording: Option<(PathBuf, u64)>,
+    /// Why the last recording stopped, if it was not stopped on purpose.
+    pub record_error: Option<String>,
+    /// Why the last push or MQTT publish failed, until one succeeds again.
+    pub push_error: Option<String>,
+    /// Called after every new sample (and remote status change), e.g. to repaint a UI that otherwise sleeps.
+    pub on_sample: Option<Box<dyn Fn() + Send>>,
+}
+
+impl History {
+    /// Empty history for `channels`, keeping `capacity` raw samples per channel taken every `period` seconds,
+    /// and aggregates beyond them (see [`crate::series`]).
+    pub fn new(channels: usize, capacity: usize, period: f64) -> Self {
+        Self {
+            store: SampleStore::new(channels, capacity),
+            raw: vec![None; channels],
+            samples: 0,
+            late: 0,
+            now: 0.0,
+            period,
+            wall_at_zero: SystemTime::now(),
+            suspends: vec![],
+            recording: None,
+            record_error: None,
+            push_error: None,
+            on_sample: None,
+        }
+    }
+
+    /// Runs [`Self::on_sample`], if set.
+    pub fn wake(&self) { if let Some(f) = &self.on_sample { f(); } }
+
+    /// Appends a recorded or streamed row at `x`; a row not after the latest one is ignored.
+    pub fn push_row(&mut self, x: f64, row: &Row) {
+        if !self.store.push(x, &row.values) { return; }
+        self.raw.clone_from(&row.values);
+        self.samples += 1;
+        self.now = x;
+        self.wall_at_zero = UNIX_EPOCH + Duration::from_secs_f64((row.unix_time - x).max(0.0));
+        self.wake();
+    }
+
+    /// Seconds back the history reaches once full: the raw samples or the aggregate tiers, whichever is longer.
+    pub fn span(&self) -> f64 { (self.store.capacity() as f64 * self.period).max(TIERED_SPAN) }
+
+    pub fn series(&self, ch: usize) -> Series<'_> { self.store.series(ch) }
+
+    /// Samples further apart than this have a hole between them.
+    pub fn max_gap(&self) -> f64 { self.period * 1.5 }
+
+    pub fn wall_at(&self, x: f64) -> SystemTime {
+        if x >= 0.0 { self.wall_at_zero + Duration::from_secs_f64(x) } else { self.wall_at_zero - Duration::from_secs_f64(-x) }
+    }
+}
+
+/// Shorter differences between the two clocks are scheduling noise, not a suspend.
+const SUSPEND_MIN: Duration = Duration::from_secs(1);
+
+enum Command { Calibrate(String, Calibration), Record(Option<Recorder>), Export(Exporter), Sink(Box<dyn Sink>) }
+
+pub struct Sampler {
+    channels: Vec<Channel>,
+    groups: Vec<SensorGroup>,
+    history: Arc<Mutex<History>>,
+    tx: Sender<Command>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Sampler {
+    /// Starts sampling every `period`, keeping the last `capacity` samples per channel. Ticks are scheduled
+    /// from the start time, so a slow read delays one sample instead of shifting all later ones; ticks that
+    /// are missed entirely are skipped rather than bunched up.
+    pub fn spawn(mut collector: Collector, period: Duration, capacity: usize) -> Self {
+        let channels = collector.channels().to_vec();
+        let groups = collector.groups().to_vec();
+        let history = Arc::new(Mutex::new(History::new(channels.len(), capacity, period.as_secs_f64())));
+        let (tx, rx) = mpsc::channel::<Command>();
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let (history, stop) = (history.clone(), stop.clone());
+            thread::Builder::new().name("sia-sampler".into()).spawn(move || {
+                let (start, boot_start) = (Instant::now(), crate::clock::boottime());
+                let mut next = start;
+                let mut prev: Option<(Instant, Duration)> = None;
+                let mut recorder: Option<Recorder> = None;
+                let mut exporters: Vec<Exporter> = vec![];
+                let mut sinks: Option<SinkThread> = None;
+                while !stop.load(Ordering::Relaxed) {
+                    for cmd in rx.try_iter() {
+                        match cmd {
+                            Command::Calibrate(id, c) => collector.calibration_mut().set(&id, c),
+                            Command::Export(e) => exporters.push(e),
+                            Command::Sink(s) => sinks.get_or_insert_with(|| {
+                                let history = history.clone();
+                                SinkThread::spawn(move |err| history.lock().unwrap_or_else(|e| e.into_inner()).push_error = err)
+                            }).add(s),
+                            Command::Record(r) => {
+                                recorder = r;
+                                let mut h = history.lock().unwrap_or_else(|e| e.into_inner());
+                                h.recording = recorder.as_ref().map(|r| (r.path().to_path_buf(), 0));
+                                h.record_error = None;
+                            }
+                        }
+                    }
+                    let snap = collector.sample();
+                    for e in &exporters { e.update(&snap); }
+                    if let Some(s) = &sinks { s.send(&snap); }
+                    let record_err = recorder.as_mut().and_then(|r| r.write(&snap).err());
+                    if record_err.is_some() { recorder = None; }
+                    let x = snap.boot.saturating_sub(boot_start).as_secs_f64();
+                    let late = snap.mono.saturating_duration_since(next) > period / 2;
+                    // CLOCK_MONOTONIC stands still while suspended, CLOCK_BOOTTIME does not
+                    let asleep = prev.map(|(mono, boot)| snap.boot.saturating_sub(boot).saturating_sub(snap.mono - mono)).unwrap_or_default();
+                    prev = Some((snap.mono, snap.boot));
+                    {
+                        let mut h = history.lock().unwrap_or_else(|e| e.into_inner());
+                        // CLOCK_BOOTTIME only moves forward, so the store never turns this tick down
+                        h.store.push(x, &snap.values);
+                        h.raw = snap.raw;
+                        if let Some(e) = record_err { h.recording = None; h.record_error = Some(e.to_string()); }
+                        if let (Some(r), Some((_, rows))) = (&recorder, h.recording.as_mut()) { *rows = r.rows(); }
+                        h.samples += 1;
+                        if late { h.late += 1; }
+                        h.now = x;
+                        h.wall_at_zero = snap.wall.checked_sub(Duration::from_secs_f64(x)).unwrap_or(snap.wall);
+                        if asleep > SUSPEND_MIN { h.suspends.push(Suspend { x, secs: asleep.as_secs_f64() }); }
+                        let oldest = h.store.first_x().unwrap_or(x);
+                        h.suspends.retain(|s| s.x >= oldest);
+                        h.wake();
+                    }
+                    next += period;
+                    let now = Instant::now();
+                    while next < now { next += period; }
+                    // sleep in short steps so dropping the sampler does not wait a whole period
+                    while !stop.load(Ordering::Relaxed) {
+                        let left = next.saturating_duration_since(Instant::now());
+                        if left.is_zero() { break; }
+                        thread::sleep(left.min(Duration::from_millis(100)));
+                    }
+                }
+                // let the sinks send what they still batch
+                drop(sinks);
+            }).expect("spawning sampler thread")
+        };
+        Self { channels, groups, history, tx, stop, handle: Some(handle) }
+    }
+
+    pub fn channels(&self) -> &[Channel] { &self.channels }
+
+    /// Shared handle to the history; lock it only for as long as it takes to read.
+    pub fn history(&self) -> Arc<Mutex<History>> { self.history.clone() }
+
+    pub fn lock(&self) -> MutexGuard<'_, History> { self.history.lock().unwrap_or_else(|e| e.into_inner()) }
+
+    /// Applies to samples taken from now on.
+    pub fn set_calibration(&self, id: &str, c: Calibration) { let _ = self.tx.send(Command::Calibrate(id.to_string(), c)); }
+
+    /// Records every sample from the next one on to `path` (see [`crate::record`]), replacing any recording
+    /// in progress. The file is created here, so a bad path is reported right away.
+    pub fn start_recording(&self, path: &Path) -> std::io::Result<()> {
+        let r = Recorder::create(path, &self.channels, &self.groups)?;
+        let _ = self.tx.send(Command::Record(Some(r)));
+        Ok(())
+    }
+
+    pub fn stop_recording(&self) { let _ = self.tx.send(Command::Record(None)); }
+
+    /// Serves Prometheus metrics on `addr` (see [`crate::metrics`]) from the next sample on.
+    pub fn listen(&self, addr: std::net::SocketAddr) -> std::io::Result<Exporter> {
+        let e = Exporter::bind(addr, &self.channels, &self.groups)?;
+        let _ = self.tx.send(Command::Export(e.clone()));
+        Ok(e)
+    }
+
+    /// Pushes every sample from the next one on (see [`crate::push`]). The target is connected here, so a bad
+    /// address is reported right away.
+    pub fn push(&self, config: PushConfig) -> std::io::Result<()> {
+        let p = Pusher::new(config, &self.channels, &self.groups)?;
+        let _ = self.tx.send(Command::Sink(Box::new(p)));
+        Ok(())
+    }
+
+    /// Publishes every sample from the next one on to an MQTT broker (see [`crate::mqtt`]), connecting here.
+    pub fn publish_mqtt(&self, config: MqttConfig) -> std::io::Result<()> {
+        let p = Publisher::new(config, &self.channels, &self.groups)?;
+        let _ = self.tx.send(Command::Sink(Box::new(p)));
+        Ok(())
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() { let _ = h.join(); }
+    }
+}
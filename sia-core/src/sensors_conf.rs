@@ -0,0 +1,317 @@
+//! lm-sensors configuration. Reads `/etc/sensors3.conf` and `/etc/sensors.d/*` (the libsensors format) so
+//! machines already tuned for `sensors` show the same names and values in sia. `label`, `ignore` and `compute`
+//! statements are applied to temperature channels; `set` only writes limits to the chip when `sensors -s` runs,
+//! so it is parsed and skipped.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A `compute` expression in libsensors syntax: `@` is the raw value, `^` is exp and `` ` `` is ln.
+#[derive(Clone, Debug)]
+pub enum Expr {
+    Raw,
+    Num(f64),
+    Neg(Box<Expr>),
+    Exp(Box<Expr>),
+    Ln(Box<Expr>),
+    Bin(char, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn eval(&self, raw: f64) -> f64 {
+        match self {
+            Expr::Raw => raw,
+            Expr::Num(v) => *v,
+            Expr::Neg(e) => -e.eval(raw),
+            Expr::Exp(e) => e.eval(raw).exp(),
+            Expr::Ln(e) => e.eval(raw).ln(),
+            Expr::Bin(op, a, b) => {
+                let (a, b) = (a.eval(raw), b.eval(raw));
+                match op { '+' => a + b, '-' => a - b, '*' => a * b, _ => a / b }
+            }
+        }
+    }
+}
+
+/// What the configuration says about one temperature channel.
+#[derive(Clone, Debug, Default)]
+pub struct Tuning { pub label: Option<String>, pub ignore: bool, pub compute: Option<Expr> }
+
+#[derive(Debug, Default)]
+struct ChipBlock { patterns: Vec<String>, labels: Vec<(String, String)>, ignores: Vec<String>, computes: Vec<(String, Expr)> }
+
+#[derive(Debug, Default)]
+pub struct SensorsConf { blocks: Vec<ChipBlock> }
+
+impl SensorsConf {
+    /// `/etc/sensors3.conf` (or the older `/etc/sensors.conf`), then `/etc/sensors.d/*` in name order;
+    /// like libsensors, later statements override earlier ones.
+    pub fn load() -> Self {
+        let mut files: Vec<PathBuf> = vec![];
+        let main = Path::new("/etc/sensors3.conf");
+        if main.exists() { files.push(main.into()); } else if Path::new("/etc/sensors.conf").exists() { files.push("/etc/sensors.conf".into()); }
+        if let Ok(rd) = fs::read_dir("/etc/sensors.d") {
+            let mut extra: Vec<PathBuf> = rd.flatten().map(|e| e.path()).filter(|p| p.is_file()).collect();
+            extra.sort();
+            files.extend(extra);
+        }
+        let mut conf = SensorsConf::default();
+        for f in files {
+            match fs::read_to_string(&f) {
+                Ok(src) => conf.blocks.extend(parse(&src, &f.display().to_string())),
+                Err(e) => eprintln!("sia: cannot read {}: {}", f.display(), e),
+            }
+        }
+        conf
+    }
+
+    /// Tuning for feature `temp{channel}` of the chip libsensors calls `chip` (e.g. `coretemp-isa-0000`).
+    pub fn tuning(&self, chip: &str, channel: u32) -> Tuning {
+        let feature = format!("temp{}", channel);
+        let mut t = Tuning::default();
+        for b in self.blocks.iter().filter(|b| b.patterns.iter().any(|p| glob_match(p, chip))) {
+            if let Some((_, l)) = b.labels.iter().rev().find(|(f, _)| *f == feature) { t.label = Some(l.clone()); }
+            if b.ignores.contains(&feature) { t.ignore = true; }
+            if let Some((_, e)) = b.computes.iter().rev().find(|(f, _)| *f == feature) { t.compute = Some(e.clone()); }
+        }
+        t
+    }
+}
+
+/// libsensors chip name of a hwmon chip: `<name>-<bus>-<address>`, e.g. `nvme-pci-0100` or `acpitz-acpi-0`.
+/// `bus_dev` is the bus device the chip hangs off (see [`crate::hwmon::bus_device`]), `hwmon_nr` the N in `hwmonN`.
+pub fn chip_name(name: &str, bus_dev: Option<&Path>, hwmon_nr: u32) -> String {
+    let virt = || format!("{}-virtual-{:x}", name, hwmon_nr);
+    let Some(dev) = bus_dev else { return virt() };
+    let subsystem = fs::canonicalize(dev.join("subsystem")).ok().and_then(|p| p.file_name().map(|s| s.to_string_lossy().into_owned())).unwrap_or_default();
+    let base = dev.file_name().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    match subsystem.as_str() {
+        "pci" => {
+            // 0000:01:00.0 -> (domain << 16) + (bus << 8) + (slot << 3) + func
+            let parts: Vec<&str> = base.split([':', '.']).collect();
+            let n = |i: usize| parts.get(i).and_then(|p| u32::from_str_radix(p, 16).ok());
+            match (n(0), n(1), n(2), n(3)) {
+                (Some(d), Some(b), Some(s), Some(f)) => format!("{}-pci-{:04x}", name, (d << 16) + (b << 8) + (s << 3) + f),
+                _ => virt(),
+            }
+        }
+        "platform" | "isa" => {
+            // coretemp.0 -> 0, it87.2608 -> 0x0a30
+            let addr = base.rsplit('.').next().and_then(|a| a.parse::<u32>().ok()).unwrap_or(0);
+            format!("{}-isa-{:04x}", name, addr)
+        }
+        "i2c" => {
+            // 1-0048 -> bus 1, address 0x48
+            match base.split_once('-').and_then(|(b, a)| Some((b.parse::<u32>().ok()?, u32::from_str_radix(a, 16).ok()?))) {
+                Some((bus, addr)) => format!("{}-i2c-{}-{:02x}", name, bus, addr),
+                None => virt(),
+            }
+        }
+        "acpi" => format!("{}-acpi-0", name),
+        _ => virt(),
+    }
+}
+
+/// Glob match with `*` wildcards, as used by `chip` statements.
+fn glob_match(pattern: &str, s: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 { return pattern == s; }
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !s.starts_with(first) || s.len() < first.len() + last.len() || !s.ends_with(last) { return false; }
+    let mut rest = &s[first.len()..s.len() - last.len()];
+    for mid in &parts[1..parts.len() - 1] {
+        match rest.find(mid) { Some(i) => rest = &rest[i + mid.len()..], None => return false }
+    }
+    true
+}
+
+// ---------- parser ----------
+
+#[derive(Clone, Debug, PartialEq)]
+enum Tok { Word(String), Str(String), Comma }
+
+fn tokenize(line: &str) -> Result<Vec<Tok>, String> {
+    let mut out = vec![];
+    let mut it = line.chars().peekable();
+    while let Some(&c) = it.peek() {
+        match c {
+            '#' => break,
+            c if c.is_whitespace() => { it.next(); }
+            ',' => { it.next(); out.push(Tok::Comma); }
+            '"' => {
+                it.next();
+                let mut s = String::new();
+                loop {
+                    match it.next() {
+                        Some('"') => break,
+                        Some('\\') => match it.next() { Some('n') => s.push('\n'), Some('t') => s.push('\t'), Some(e) => s.push(e), None => return Err("unterminated string".into()) },
+                        Some(ch) => s.push(ch),
+                        None => return Err("unterminated string".into()),
+                    }
+                }
+                out.push(Tok::Str(s));
+            }
+            _ => {
+                let mut w = String::new();
+                while let Some(&ch) = it.peek() { if ch.is_whitespace() || ch == ',' || ch == '"' || ch == '#' { break; } w.push(ch); it.next(); }
+                out.push(Tok::Word(w));
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn parse(src: &str, origin: &str) -> Vec<ChipBlock> {
+    let mut blocks: Vec<ChipBlock> = vec![];
+    for (n, line) in src.lines().enumerate() {
+        let warn = |msg: &str| eprintln!("sia: {}:{}: {}", origin, n + 1, msg);
+        let toks = match tokenize(line) { Ok(t) => t, Err(e) => { warn(&e); continue; } };
+        let Some(Tok::Word(kw)) = toks.first() else { continue };
+        let args = &toks[1..];
+        let word = |t: Option<&Tok>| match t { Some(Tok::Word(w)) | Some(Tok::Str(w)) => Some(w.clone()), _ => None };
+        match kw.as_str() {
+            "chip" => {
+                let patterns: Vec<String> = args.iter().filter_map(|t| word(Some(t))).collect();
+                blocks.push(ChipBlock { patterns, ..Default::default() });
+            }
+            "label" | "ignore" | "compute" => {
+                let Some(block) = blocks.last_mut() else { warn("statement outside a chip block"); continue };
+                let Some(feature) = word(args.first()) else { warn("missing feature name"); continue };
+                match kw.as_str() {
+                    "label" => match word(args.get(1)) { Some(l) => block.labels.push((feature, l)), None => warn("label without text") },
+                    "ignore" => block.ignores.push(feature),
+                    _ => {
+                        // only the raw -> displayed half is needed; the inverse is for writing limits
+                        let from: String = args[1..].iter().take_while(|t| **t != Tok::Comma).filter_map(|t| word(Some(t))).collect::<Vec<_>>().join(" ");
+                        match parse_expr(&from) { Ok(e) => block.computes.push((feature, e)), Err(e) => warn(&format!("compute {}: {}", feature, e)) }
+                    }
+                }
+            }
+            "set" | "bus" => {}
+            other => warn(&format!("unknown statement {:?}", other)),
+        }
+    }
+    blocks
+}
+
+fn parse_expr(src: &str) -> Result<Expr, String> {
+    let chars: Vec<char> = src.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut pos = 0;
+    let e = expr(&chars, &mut pos)?;
+    if pos != chars.len() { return Err(format!("unexpected {:?}", chars[pos])); }
+    Ok(e)
+}
+
+fn expr(c: &[char], pos: &mut usize) -> Result<Expr, String> {
+    let mut lhs = term(c, pos)?;
+    while let Some(&op) = c.get(*pos).filter(|ch| **ch == '+' || **ch == '-') {
+        *pos += 1;
+        lhs = Expr::Bin(op, Box::new(lhs), Box::new(term(c, pos)?));
+    }
+    Ok(lhs)
+}
+
+fn term(c: &[char], pos: &mut usize) -> Result<Expr, String> {
+    let mut lhs = factor(c, pos)?;
+    while let Some(&op) = c.get(*pos).filter(|ch| **ch == '*' || **ch == '/') {
+        *pos += 1;
+        lhs = Expr::Bin(op, Box::new(lhs), Box::new(factor(c, pos)?));
+    }
+    Ok(lhs)
+}
+
+fn factor(c: &[char], pos: &mut usize) -> Result<Expr, String> {
+    let Some(&ch) = c.get(*pos) else { return Err("unexpected end".into()) };
+    *pos += 1;
+    match ch {
+        '@' => Ok(Expr::Raw),
+        '-' => Ok(Expr::Neg(Box::new(factor(c, pos)?))),
+        '^' => Ok(Expr::Exp(Box::new(factor(c, pos)?))),
+        '`' => Ok(Expr::Ln(Box::new(factor(c, pos)?))),
+        '(' => {
+            let e = expr(c, pos)?;
+            if c.get(*pos) != Some(&')') { return Err("missing )".into()); }
+            *pos += 1;
+            Ok(e)
+        }
+        d if d.is_ascii_digit() || d == '.' => {
+            let start = *pos - 1;
+            while c.get(*pos).map(|x| x.is_ascii_digit() || *x == '.').unwrap_or(false) { *pos += 1; }
+            let s: String = c[start..*pos].iter().collect();
+            s.parse::<f64>().map(Expr::Num).map_err(|_| format!("bad number {:?}", s))
+        }
+        // references to other features (e.g. `in3`) need their values at evaluation time; not supported
+        other => Err(format!("unsupported {:?}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(src: &str, raw: f64) -> f64 { parse_expr(src).unwrap().eval(raw) }
+
+    #[test]
+    fn compute_precedence() {
+        assert_eq!(eval("@+2*3", 1.0), 7.0);
+        assert_eq!(eval("(@+2)*3", 1.0), 9.0);
+        assert_eq!(eval("@-2-3", 10.0), 5.0);
+        assert_eq!(eval("@/2/5", 100.0), 10.0);
+        assert_eq!(eval(" @ * 0.5 + 10 ", 40.0), 30.0);
+    }
+
+    #[test]
+    fn compute_unary() {
+        assert_eq!(eval("-@", 3.0), -3.0);
+        assert_eq!(eval("-@*2", 3.0), -6.0);
+        assert_eq!(eval("@--2", 3.0), 5.0);
+        assert!((eval("^`@", 7.0) - 7.0).abs() < 1e-12);
+        assert!((eval("`(^2)", 0.0) - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn compute_errors() {
+        assert!(parse_expr("@+").is_err());
+        assert!(parse_expr("(@+1").is_err());
+        assert!(parse_expr("@ 1").is_err());
+        assert!(parse_expr("in3*2").is_err());
+    }
+
+    #[test]
+    fn statements() {
+        let conf = SensorsConf { blocks: parse("chip \"coretemp-*\" \"k10temp-*\"\n  label temp1 \"Package\"\n  compute temp1 @*2, @/2\n  ignore temp2\n  set temp1_max 90\n", "test") };
+        let t = conf.tuning("coretemp-isa-0000", 1);
+        assert_eq!(t.label.as_deref(), Some("Package"));
+        assert_eq!(t.compute.map(|e| e.eval(20.0)), Some(40.0));
+        assert!(conf.tuning("k10temp-pci-00c3", 2).ignore);
+        assert!(conf.tuning("nvme-pci-0100", 1).label.is_none());
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("nct*-isa-*", "nct6798-isa-0290"));
+        assert!(!glob_match("nct*-isa-*", "nct6798-pci-0290"));
+        assert!(!glob_match("a*a", "a"));
+    }
+
+    /// A fake bus device `base` whose `subsystem` link points at a directory named `subsystem`.
+    fn device(subsystem: &str, base: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("sia-chip-{}-{}-{}", std::process::id(), subsystem, base));
+        let (bus, dev) = (root.join("bus").join(subsystem), root.join("devices").join(base));
+        fs::create_dir_all(&bus).unwrap();
+        fs::create_dir_all(&dev).unwrap();
+        let _ = fs::remove_file(dev.join("subsystem"));
+        std::os::unix::fs::symlink(&bus, dev.join("subsystem")).unwrap();
+        dev
+    }
+
+    #[test]
+    fn chip_names() {
+        assert_eq!(chip_name("nvme", Some(&device("pci", "0000:01:00.0")), 3), "nvme-pci-0100");
+        assert_eq!(chip_name("amdgpu", Some(&device("pci", "0000:0a:00.1")), 3), "amdgpu-pci-0a01");
+        assert_eq!(chip_name("coretemp", Some(&device("platform", "coretemp.0")), 3), "coretemp-isa-0000");
+        assert_eq!(chip_name("it87", Some(&device("isa", "it87.2608")), 3), "it87-isa-0a30");
+        assert_eq!(chip_name("lm75", Some(&device("i2c", "1-0048")), 3), "lm75-i2c-1-48");
+        assert_eq!(chip_name("acpitz", None, 10), "acpitz-virtual-a");
+    }
+}
@@ -0,0 +1,359 @@
+//! Columnar sample store with tiered storage. Every channel is sampled on the same tick, so a [`SampleStore`]
+//! keeps one timestamp column for all of them and one `f32` value column per channel: the last `cap` raw
+//! samples, then min/avg/max aggregates per 10 s and per minute (see [`TIERS`]) for much longer views at a
+//! bounded size. A sample with no data (failed read) is stored as NaN at its time, so every consumer sees the
+//! same hole: plots break the line there and `missed` counts it.
+//!
+//! A [`Series`] reads one channel. Queries pick the finest tier that reaches back to `x_min` and finish with
+//! the raw samples the aggregates do not cover yet. For plotting, segments can be decimated to the plot's
+//! width ([`Lod`]): per pixel column only the first, lowest, highest and last point are kept, so the line
+//! looks the same and every spike stays visible at any zoom. Segments are cached per query until the next
+//! sample, so redrawing between samples costs a lookup rather than a pass over the columns.
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Aggregate tiers: bucket length in seconds and buckets kept, 2 hours of 10 s and a day of 1 min.
+pub const TIERS: [(f64, usize); 2] = [(10.0, 720), (60.0, 1440)];
+
+/// How far back the aggregate tiers reach.
+pub const TIERED_SPAN: f64 = TIERS[TIERS.len() - 1].0 * TIERS[TIERS.len() - 1].1 as f64;
+
+/// Runs of `[x, y]` points, split at holes.
+pub type Segments = Arc<Vec<Vec<[f64; 2]>>>;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Col { Min, Avg, Max }
+
+/// Level of detail for plotting: the visible x range ends at `x_max` and is `px` pixels wide.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lod { pub x_max: f64, pub px: usize }
+
+/// One channel's share of the bucket still taking samples.
+#[derive(Clone, Copy)]
+struct Acc { n: u32, sum: f64, min: f64, max: f64 }
+
+impl Acc {
+    const EMPTY: Acc = Acc { n: 0, sum: 0.0, min: f64::NAN, max: f64::NAN };
+}
+
+/// Closed buckets of one step; `xs` are bucket centres, an all-missing bucket is NaN.
+struct Tier {
+    step: f64,
+    cap: usize,
+    xs: VecDeque<f64>,
+    min: Vec<VecDeque<f32>>,
+    avg: Vec<VecDeque<f32>>,
+    max: Vec<VecDeque<f32>>,
+    /// Start of the open bucket, and what each channel has put in it.
+    open: Option<f64>,
+    acc: Vec<Acc>,
+}
+
+impl Tier {
+    fn new(channels: usize, step: f64, cap: usize) -> Self {
+        let cols = || (0..channels).map(|_| VecDeque::new()).collect();
+        Self { step, cap, xs: VecDeque::new(), min: cols(), avg: cols(), max: cols(), open: None, acc: vec![Acc::EMPTY; channels] }
+    }
+
+    fn push(&mut self, x: f64, values: &[Option<f64>]) {
+        let start = (x / self.step).floor() * self.step;
+        if let Some(s) = self.open.filter(|s| *s != start) { self.close(s); }
+        self.open = Some(start);
+        for (a, v) in self.acc.iter_mut().zip(values) {
+            // f64::min/max skip NaN
+            if let Some(y) = v.filter(|y| !y.is_nan()) { a.n += 1; a.sum += y; a.min = a.min.min(y); a.max = a.max.max(y); }
+        }
+    }
+
+    fn close(&mut self, start: f64) {
+        if self.xs.len() == self.cap {
+            self.xs.pop_front();
+            for c in self.min.iter_mut().chain(&mut self.avg).chain(&mut self.max) { c.pop_front(); }
+        }
+        self.xs.push_back(start + self.step / 2.0);
+        for (i, a) in self.acc.iter_mut().enumerate() {
+            self.min[i].push_back(a.min as f32);
+            self.avg[i].push_back(if a.n > 0 { (a.sum / a.n as f64) as f32 } else { f32::NAN });
+            self.max[i].push_back(a.max as f32);
+            *a = Acc::EMPTY;
+        }
+    }
+
+    /// Where the closed buckets end; raw samples from here on are not aggregated yet.
+    fn end(&self) -> f64 { self.xs.back().map(|x| x + self.step / 2.0).unwrap_or(f64::NEG_INFINITY) }
+
+    fn col(&self, c: Col, ch: usize) -> &VecDeque<f32> { match c { Col::Min => &self.min[ch], Col::Avg => &self.avg[ch], Col::Max => &self.max[ch] } }
+}
+
+/// Segment lists cached per channel: enough for every plot a channel is drawn in, while a window that
+/// moves or resizes between samples replaces old entries instead of piling them up.
+const CACHE_PER_CHANNEL: usize = 8;
+
+/// What a cached segment list was built from.
+#[derive(Clone, Copy, PartialEq)]
+struct Key { col: Col, x_min: f64, div: f64, max_gap: f64, lod: Option<Lod> }
+
+pub struct SampleStore {
+    cap: usize,
+    xs: VecDeque<f64>,
+    cols: Vec<VecDeque<f32>>,
+    tiers: Vec<Tier>,
+    missed: Vec<u64>,
+    /// Samples pushed so far; the cache is only valid for the count it was built at.
+    pushed: u64,
+    cached_at: Cell<u64>,
+    cache: RefCell<Vec<Vec<(Key, Segments)>>>,
+}
+
+impl SampleStore {
+    /// Empty store for `channels`, keeping `cap` raw samples.
+    pub fn new(channels: usize, cap: usize) -> Self {
+        Self {
+            cap,
+            xs: VecDeque::with_capacity(cap),
+            cols: (0..channels).map(|_| VecDeque::with_capacity(cap)).collect(),
+            tiers: TIERS.iter().map(|&(step, n)| Tier::new(channels, step, n)).collect(),
+            missed: vec![0; channels],
+            pushed: 0,
+            cached_at: Cell::new(0),
+            cache: RefCell::new(vec![vec![]; channels]),
+        }
+    }
+
+    /// Appends one tick; `None` (or NaN) is a failed read. A tick not after the last one is dropped and gives
+    /// `false`, so the columns stay sorted by x.
+    pub fn push(&mut self, x: f64, values: &[Option<f64>]) -> bool {
+        if x.is_nan() || self.xs.back().is_some_and(|b| x <= *b) { return false; }
+        if self.xs.len() == self.cap {
+            self.xs.pop_front();
+            for c in &mut self.cols { c.pop_front(); }
+        }
+        self.xs.push_back(x);
+        for ((c, m), v) in self.cols.iter_mut().zip(&mut self.missed).zip(values.iter().chain(std::iter::repeat(&None))) {
+            let y = v.filter(|y| !y.is_nan());
+            if y.is_none() { *m += 1; }
+            c.push_back(y.map(|y| y as f32).unwrap_or(f32::NAN));
+        }
+        for t in &mut self.tiers { t.push(x, values); }
+        self.pushed += 1;
+        true
+    }
+
+    pub fn channels(&self) -> usize { self.cols.len() }
+    /// Raw samples kept per channel.
+    pub fn capacity(&self) -> usize { self.cap }
+    pub fn series(&self, ch: usize) -> Series<'_> { Series { store: self, ch } }
+
+    /// The oldest x still held in any tier.
+    pub fn first_x(&self) -> Option<f64> {
+        self.tiers.iter().filter_map(|t| t.xs.front().map(|x| x - t.step / 2.0)).chain(self.xs.front().copied()).reduce(f64::min)
+    }
+
+    /// The tier to read from `x_min` on: `None` while the raw samples reach back that far.
+    fn tier(&self, x_min: f64) -> Option<&Tier> {
+        let covers = |front: Option<&f64>, full: bool| !full || front.is_some_and(|x| *x <= x_min);
+        if covers(self.xs.front(), self.xs.len() == self.cap) { return None; }
+        self.tiers.iter().find(|t| covers(t.xs.front(), t.xs.len() == t.cap)).or(self.tiers.last())
+    }
+}
+
+/// One channel of a [`SampleStore`].
+#[derive(Clone, Copy)]
+pub struct Series<'a> { store: &'a SampleStore, ch: usize }
+
+impl Series<'_> {
+    /// Calls `f(x, y, max_gap)` for every point from `x_min` on: the tier's buckets, then the raw samples after
+    /// them.
+    fn each(&self, x_min: f64, col: Col, raw_gap: f64, mut f: impl FnMut(f64, f64, f64)) {
+        let s = self.store;
+        let (mut raw_from, mut gap) = (x_min, raw_gap);
+        if let Some(t) = s.tier(x_min) {
+            for (x, y) in t.xs.iter().zip(t.col(col, self.ch)) { if *x >= x_min { f(*x, *y as f64, t.step * 1.5); } }
+            // the first raw sample is judged against the last bucket
+            (raw_from, gap) = (raw_from.max(t.end()), t.step * 1.5);
+        }
+        let start = s.xs.partition_point(|x| *x < raw_from);
+        for (x, y) in s.xs.range(start..).zip(s.cols[self.ch].range(start..)) { f(*x, *y as f64, gap); gap = raw_gap; }
+    }
+
+    fn segments(&self, col: Col, x_min: f64, div: f64, max_gap: f64, lod: Option<Lod>) -> Segments {
+        let s = self.store;
+        if s.cached_at.get() != s.pushed {
+            for c in s.cache.borrow_mut().iter_mut() { c.clear(); }
+            s.cached_at.set(s.pushed);
+        }
+        let key = Key { col, x_min, div, max_gap, lod };
+        if let Some((_, segs)) = s.cache.borrow()[self.ch].iter().find(|(k, _)| *k == key) { return segs.clone(); }
+        let mut out: Vec<Vec<[f64; 2]>> = vec![];
+        let mut prev = f64::NEG_INFINITY;
+        let mut broken = true;
+        self.each(x_min, col, max_gap, |x, y, gap| {
+            if y.is_nan() { broken = true; return; }
+            if broken || x - prev > gap { out.push(vec![]); }
+            if let Some(seg) = out.last_mut() { seg.push([x, y / div]); }
+            prev = x;
+            broken = false;
+        });
+        if let Some(lod) = lod { out = out.into_iter().map(|seg| decimate(seg, x_min, lod)).collect(); }
+        let segs = Arc::new(out);
+        let mut cache = s.cache.borrow_mut();
+        let c = &mut cache[self.ch];
+        if c.len() == CACHE_PER_CHANNEL { c.remove(0); }
+        c.push((key, segs.clone()));
+        segs
+    }
+
+    /// Failed reads since the store was created, including ones that have scrolled out.
+    pub fn missed(&self) -> u64 { self.store.missed[self.ch] }
+    /// Points from `x_min` on (bucket averages where older than the raw samples); missing samples are NaN.
+    pub fn points_after(&self, x_min: f64) -> Vec<[f64; 2]> { self.points_after_scaled(x_min, 1.0) }
+    pub fn points_after_scaled(&self, x_min: f64, div: f64) -> Vec<[f64; 2]> {
+        let mut out: Vec<[f64; 2]> = Vec::with_capacity(self.store.xs.len());
+        self.each(x_min, Col::Avg, 0.0, |x, y, _| out.push([x, y / div]));
+        out
+    }
+    /// Like [`Self::points_after_scaled`], but split into runs at missing samples and wherever consecutive
+    /// samples are more than `max_gap` apart (1.5 buckets for aggregates), so a plot shows the hole instead
+    /// of a line across it.
+    pub fn segments_after_scaled(&self, x_min: f64, div: f64, max_gap: f64) -> Segments { self.segments(Col::Avg, x_min, div, max_gap, None) }
+    /// [`Self::segments_after_scaled`] decimated to `lod`.
+    pub fn segments_lod(&self, x_min: f64, div: f64, max_gap: f64, lod: Lod) -> Segments { self.segments(Col::Avg, x_min, div, max_gap, Some(lod)) }
+    /// Bucket minima and maxima as segments decimated to `lod`, for drawing the spread around the averages;
+    /// `None` while the raw samples reach back to `x_min`.
+    pub fn extremes_lod(&self, x_min: f64, div: f64, max_gap: f64, lod: Lod) -> Option<[Segments; 2]> {
+        self.store.tier(x_min)?;
+        Some([self.segments(Col::Min, x_min, div, max_gap, Some(lod)), self.segments(Col::Max, x_min, div, max_gap, Some(lod))])
+    }
+    /// Includes bucket extremes, so a spike older than the raw samples still widens the range.
+    pub fn min_max_y(&self, x_min: f64, x_max: f64) -> Option<(f64, f64)> {
+        let mut mn = f64::INFINITY; let mut mx = f64::NEG_INFINITY;
+        // f64::min/max skip NaN
+        self.each(x_min, Col::Min, 0.0, |x, y, _| if x <= x_max { mn = mn.min(y); });
+        self.each(x_min, Col::Max, 0.0, |x, y, _| if x <= x_max { mx = mx.max(y); });
+        if mn.is_finite() && mx.is_finite() { Some((mn, mx)) } else { None }
+    }
+    /// The latest sample, `None` if it is missing.
+    pub fn last_y(&self) -> Option<f64> { self.store.cols[self.ch].back().map(|y| *y as f64).filter(|y| !y.is_nan()) }
+}
+
+/// Keeps the first, lowest, highest and last point of each pixel column, in x order. Segments with few points
+/// per column are returned as they are.
+fn decimate(seg: Vec<[f64; 2]>, x_min: f64, lod: Lod) -> Vec<[f64; 2]> {
+    let px = lod.px.max(1);
+    if seg.len() <= 4 * px || lod.x_max <= x_min { return seg; }
+    let col = |x: f64| (((x - x_min) / (lod.x_max - x_min) * px as f64) as usize).min(px - 1);
+    let mut out = Vec::with_capacity(4 * px);
+    let mut i = 0;
+    while i < seg.len() {
+        let (first, c) = (i, col(seg[i][0]));
+        let (mut lo, mut hi) = (i, i);
+        while i < seg.len() && col(seg[i][0]) == c {
+            if seg[i][1] < seg[lo][1] { lo = i; }
+            if seg[i][1] > seg[hi][1] { hi = i; }
+            i += 1;
+        }
+        let mut keep = [first, lo, hi, i - 1];
+        keep.sort_unstable();
+        for (k, j) in keep.iter().enumerate() { if k == 0 || keep[k - 1] != *j { out.push(seg[*j]); } }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A store with one channel, fed `y = x` once a second for x in `xs`.
+    fn store(cap: usize, xs: std::ops::Range<u32>) -> SampleStore {
+        let mut s = SampleStore::new(1, cap);
+        for x in xs { s.push(x as f64, &[Some(x as f64)]); }
+        s
+    }
+
+    #[test]
+    fn tier_rollover() {
+        let mut t = Tier::new(1, 10.0, 3);
+        for x in 0..45 { t.push(x as f64, &[Some(x as f64)]); }
+        // [0, 10) was pushed out; [40, 50) is still open
+        assert_eq!(t.xs, [15.0, 25.0, 35.0]);
+        assert_eq!((t.min[0][2], t.avg[0][2], t.max[0][2]), (30.0, 34.5, 39.0));
+        assert_eq!(t.end(), 40.0);
+        for x in 45..60 { t.push(x as f64, &[if x < 50 { Some(1.0) } else { None }]); }
+        assert_eq!(t.xs, [25.0, 35.0, 45.0]);
+        assert_eq!(t.min[0][2], 1.0);
+        // an open bucket with no readings closes as NaN
+        t.push(60.0, &[Some(0.0)]);
+        assert!(t.avg[0][2].is_nan() && t.min[0][2].is_nan() && t.max[0][2].is_nan());
+    }
+
+    #[test]
+    fn tier_choice() {
+        let s = store(100, 0..50);
+        assert!(s.tier(0.0).is_none(), "raw samples not full yet");
+        let s = store(100, 0..8000);
+        assert!(s.tier(7950.0).is_none(), "raw samples reach back");
+        assert_eq!(s.tier(5000.0).map(|t| t.step), Some(10.0));
+        assert_eq!(s.tier(100.0).map(|t| t.step), Some(60.0));
+        assert_eq!(s.tier(-1e9).map(|t| t.step), Some(60.0));
+    }
+
+    #[test]
+    fn tiers_then_raw() {
+        let s = store(100, 0..1000);
+        let pts = s.series(0).points_after(500.0);
+        assert!(pts.windows(2).all(|w| w[0][0] < w[1][0]), "x strictly increasing");
+        assert_eq!(pts[0], [505.0, 504.5]);
+        assert_eq!(pts.last(), Some(&[999.0, 999.0]));
+        // buckets up to the last closed one, [980, 990), then every raw sample after it
+        assert_eq!(pts.iter().rev().nth(10), Some(&[985.0, 984.5]));
+        assert_eq!(pts.iter().filter(|p| p[0] >= 990.0).count(), 10);
+        assert_eq!(s.series(0).segments_after_scaled(500.0, 1.0, 1.5).len(), 1, "no hole at the junction");
+    }
+
+    #[test]
+    fn holes() {
+        let mut s = SampleStore::new(1, 100);
+        for x in 0..10 { s.push(x as f64, &[if x == 4 { None } else { Some(1.0) }]); }
+        s.push(20.0, &[Some(1.0)]);
+        let segs = s.series(0).segments_after_scaled(0.0, 1.0, 1.5);
+        assert_eq!(segs.iter().map(Vec::len).collect::<Vec<_>>(), [4, 5, 1]);
+        assert_eq!(s.series(0).missed(), 1);
+    }
+
+    #[test]
+    fn push_keeps_x_sorted() {
+        let mut s = store(100, 0..10);
+        assert!(!s.push(9.0, &[Some(0.0)]));
+        assert!(!s.push(3.0, &[Some(0.0)]));
+        assert!(s.push(9.5, &[Some(0.0)]));
+        assert_eq!(s.series(0).points_after(0.0).len(), 11);
+    }
+
+    #[test]
+    fn decimate_keeps_extremes() {
+        let mut seg: Vec<[f64; 2]> = (0..10_000).map(|i| [i as f64, (i % 10) as f64]).collect();
+        seg[1234][1] = 500.0;
+        seg[8765][1] = -500.0;
+        let lod = Lod { x_max: 10_000.0, px: 50 };
+        let d = decimate(seg.clone(), 0.0, lod);
+        assert!(d.len() <= 4 * lod.px);
+        assert!(d.contains(&[1234.0, 500.0]) && d.contains(&[8765.0, -500.0]));
+        assert_eq!((d.first(), d.last()), (seg.first(), seg.last()));
+        assert!(d.windows(2).all(|w| w[0][0] < w[1][0]));
+        // few points per pixel: unchanged
+        assert_eq!(decimate(seg[..100].to_vec(), 0.0, lod), &seg[..100]);
+    }
+
+    #[test]
+    fn cache() {
+        let mut s = store(100, 0..100);
+        let lod = Lod { x_max: 100.0, px: 10 };
+        let a = s.series(0).segments_lod(0.0, 1.0, 1.5, lod);
+        assert!(Arc::ptr_eq(&a, &s.series(0).segments_lod(0.0, 1.0, 1.5, lod)));
+        for i in 0..50 { s.series(0).segments_lod(i as f64 * 0.1, 1.0, 1.5, lod); }
+        assert_eq!(s.cache.borrow()[0].len(), CACHE_PER_CHANNEL);
+        s.push(100.0, &[Some(0.0)]);
+        assert!(!Arc::ptr_eq(&a, &s.series(0).segments_lod(0.0, 1.0, 1.5, lod)), "a new sample invalidates");
+    }
+}
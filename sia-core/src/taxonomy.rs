@@ -0,0 +1,135 @@
+//! Grouping and naming: turns discovered sensors into per-device [`SensorGroup`]s using the rule table.
+use crate::hwmon::TempSensor;
+use crate::rules::Rules;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// An sRGB color, so the taxonomy's colors do not tie the library to a UI toolkit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    pub const WHITE: Rgb = Rgb(255, 255, 255);
+}
+
+/// Lightens `c` towards white by `factor` (0..1).
+pub fn tint(c: Rgb, factor: f32) -> Rgb {
+    let t = |v: u8| -> u8 { (v as f32 + (255.0 - v as f32) * factor).clamp(0.0, 255.0) as u8 };
+    Rgb(t(c.0), t(c.1), t(c.2))
+}
+
+pub fn palette() -> Vec<Rgb> { vec![ Rgb(244,67,54), Rgb(33,150,243), Rgb(76,175,80), Rgb(255,152,0), Rgb(156,39,176), Rgb(121,85,72), Rgb(63,81,181), Rgb(0,150,136), Rgb(205,220,57), Rgb(233,30,99), Rgb(158,158,158), Rgb(255,87,34), Rgb(3,169,244), Rgb(139,195,74), Rgb(171,71,188), Rgb(255,238,88), Rgb(38,198,218), Rgb(141,110,99), Rgb(120,144,156) ] }
+
+/// `id` is the stable sensor id to key settings by; `idx` is the collector's channel index for this run.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SensorItem { pub id: String, pub name: String, pub idx: usize, pub visible: bool, pub color: Rgb }
+
+/// One physical device. `kind` is the rule table's group key (drives UI layout); `key` adds the device path
+/// so two NVMe drives or two CPU sockets stay apart. `socket` is set when there is more than one CPU.
+/// `items` are temperatures, `freqs` the clocks of the same device (CPU cores, GPU clocks).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SensorGroup { pub key: String, pub kind: String, pub display: String, pub items: Vec<SensorItem>, pub freqs: Vec<SensorItem>, pub visible: bool, pub warn: f64, pub hot: f64, pub color: Rgb, pub rank: i32, pub show_thresholds: bool, pub socket: Option<usize> }
+
+impl SensorGroup {
+    /// An empty group for a device that has clocks or a GPU backend but no hwmon temperatures.
+    pub fn empty(key: String, driver: &str, rules: &Rules) -> Self {
+        let c = rules.classify(driver, driver, "");
+        SensorGroup { key, kind: c.group, display: c.display, items: vec![], freqs: vec![], visible: true, warn: c.warn, hot: c.hot, color: c.color, rank: c.rank, show_thresholds: false, socket: None }
+    }
+}
+
+/// Orders "Core 2" before "Core 10".
+pub fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return std::cmp::Ordering::Equal,
+            (None, Some(_)) => return std::cmp::Ordering::Less,
+            (Some(_), None) => return std::cmp::Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let num = |it: &mut std::iter::Peekable<std::str::Chars>| { let mut n = 0u64; while let Some(d) = it.peek().and_then(|c| c.to_digit(10)) { n = n.saturating_mul(10).saturating_add(d as u64); it.next(); } n };
+                let (nx, ny) = (num(&mut a), num(&mut b));
+                if nx != ny { return nx.cmp(&ny); }
+            }
+            (Some(x), Some(y)) => { if x != y { return x.cmp(&y); } a.next(); b.next(); }
+        }
+    }
+}
+
+/// Groups hwmon temperatures by physical device. Item `idx` is the sensor's index in `temps`, which the
+/// collector also uses as its channel index.
+pub fn build_groups(temps: &[TempSensor], rules: &Rules) -> Vec<SensorGroup> {
+    let mut map: BTreeMap<String, SensorGroup> = BTreeMap::new();
+    let mut idents: BTreeMap<String, (Option<String>, Option<String>)> = BTreeMap::new();
+    let mut hints: BTreeMap<String, (i32, bool)> = BTreeMap::new(); // sensor id -> (order, show)
+    for (idx, s) in temps.iter().enumerate() {
+        let c = rules.classify(&s.raw_name, &s.raw_label, &s.device);
+        // one group per physical device; thermal zones are virtual and share one group per kind
+        let key = if s.device.starts_with("virtual/") { c.group.clone() } else { format!("{}@{}", c.group, s.device) };
+        idents.entry(key.clone()).or_insert_with(|| (s.model.clone(), s.serial.clone()));
+        let entry = map.entry(key.clone()).or_insert(SensorGroup {
+            key,
+            kind: c.group.clone(),
+            display: c.display.clone(),
+            items: vec![],
+            visible: true,
+            warn: c.warn,
+            hot: c.hot,
+            color: c.color,
+            rank: c.rank,
+            show_thresholds: false,
+            socket: None,
+            freqs: vec![],
+        });
+        hints.insert(s.id.clone(), (c.order, c.show));
+        let name = if s.labelled { s.raw_label.clone() } else { c.label };
+        entry.items.push(SensorItem { id: s.id.clone(), name, idx, visible: false, color: Rgb::WHITE });
+    }
+
+    // titles: model name when the device publishes one, otherwise number identical devices (CPU sockets)
+    let mut per_kind: BTreeMap<String, usize> = BTreeMap::new();
+    for g in map.values() { *per_kind.entry(g.kind.clone()).or_default() += 1; }
+    let mut ordinal: BTreeMap<String, usize> = BTreeMap::new();
+    for g in map.values_mut() {
+        let n = ordinal.entry(g.kind.clone()).or_default();
+        let (model, _) = &idents[&g.key];
+        if let Some(model) = model { g.display = format!("{} ({})", g.display, model); }
+        else if per_kind[&g.kind] > 1 && g.kind == "cpu" { g.display = format!("CPU (Socket {})", n); g.socket = Some(*n); }
+        else if per_kind[&g.kind] > 1 { g.display = format!("{} #{}", g.display, *n + 1); }
+        *n += 1;
+    }
+    // two drives of the same model: the serial tells them apart
+    let mut titles: BTreeMap<String, usize> = BTreeMap::new();
+    for g in map.values() { *titles.entry(g.display.clone()).or_default() += 1; }
+    for g in map.values_mut() {
+        if titles[&g.display] > 1 { if let (_, Some(serial)) = &idents[&g.key] { g.display = format!("{} [{}]", g.display, serial); } }
+    }
+
+    // sort items within groups by rule order, then by name
+    for g in map.values_mut() {
+        g.items.sort_by(|a, b| hints[&a.id].0.cmp(&hints[&b.id].0).then_with(|| natural_cmp(&a.name, &b.name)));
+    }
+
+    // defaults: whatever the rules mark `show`, else the group's first sensor
+    for g in map.values_mut() {
+        for it in &mut g.items { it.visible = hints[&it.id].1; }
+        if !g.items.iter().any(|it| it.visible) { if let Some(first) = g.items.first_mut() { first.visible = true; } }
+    }
+
+    // assign colors per group with gentle tints so the same thing stays the same color across plots;
+    // a second device of the same kind starts from a lighter shade
+    let mut ordinal: BTreeMap<String, usize> = BTreeMap::new();
+    for g in map.values_mut() {
+        let n = ordinal.entry(g.kind.clone()).or_default();
+        let base = tint(g.color, (*n as f32) * 0.2);
+        for (i, it) in g.items.iter_mut().enumerate() {
+            it.color = tint(base, (i as f32) * 0.08);
+        }
+        *n += 1;
+    }
+
+    // collect & order groups by rule rank (CPU, GPU, NVMe SSD, Memory (SPD), Wi‑Fi, Ethernet, others)
+    let mut v: Vec<_> = map.into_values().collect();
+    v.sort_by_key(|g| g.rank);
+    v
+}
@@ -0,0 +1,89 @@
+//! `sia agent`: samples this machine and streams every sample, with the sensor metadata, to viewers
+//! (`sia --connect`). The stream is a JSON Lines recording (see `sia_core::record`), over TCP to any number of
+//! viewers or over stdout for `ssh HOST sia agent --stdio`. A viewer that connects late first gets the
+//! last few minutes. Each viewer is written from its own thread, so a slow one never holds up sampling.
+use sia_core::record::Format;
+use sia_core::{Channel, Collector, CollectorConfig, Recorder, SensorGroup, Snapshot};
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub struct Options { pub bind: SocketAddr, pub stdio: bool, pub interval: f64, pub collectors: CollectorConfig }
+
+/// Samples sent to a viewer when it connects.
+const BACKLOG: usize = 5 * 60;
+
+/// Samples a viewer may fall behind by before it is dropped.
+const QUEUE: usize = 64;
+
+#[derive(Default)]
+struct Viewers { queues: Vec<SyncSender<Snapshot>>, backlog: VecDeque<Snapshot> }
+
+/// Registers a viewer: it gets the backlog, then every new sample, written to `r` from its own thread.
+fn join(viewers: &Mutex<Viewers>, mut r: Recorder) -> io::Result<()> {
+    let (tx, rx) = mpsc::sync_channel(QUEUE);
+    // snapshot the backlog and queue up under one lock, so no sample is missed or sent twice
+    let backlog: Vec<Snapshot> = {
+        let mut v = viewers.lock().unwrap_or_else(|e| e.into_inner());
+        v.queues.push(tx);
+        v.backlog.iter().cloned().collect()
+    };
+    // the thread ends when a write fails or the sampling loop drops the queue
+    thread::Builder::new().name("sia-viewer".into()).spawn(move || {
+        for s in backlog.into_iter().chain(rx) { if r.write(&s).is_err() { return; } }
+    })?;
+    Ok(())
+}
+
+pub fn run(opts: &Options) -> io::Result<()> {
+    let mut c = Collector::new(&opts.collectors);
+    let (channels, groups) = (c.channels().to_vec(), c.groups().to_vec());
+    let viewers = Arc::new(Mutex::new(Viewers::default()));
+    if opts.stdio {
+        join(&viewers, Recorder::to_writer(Box::new(io::stdout()), Format::JsonLines, Path::new("-"), &channels, &groups)?)?;
+    } else {
+        let listener = TcpListener::bind(opts.bind).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", opts.bind, e)))?;
+        eprintln!("sia agent: listening on {}", listener.local_addr()?);
+        let viewers = viewers.clone();
+        thread::Builder::new().name("sia-agent".into()).spawn(move || accept(listener, &channels, &groups, &viewers))?;
+    }
+
+    // CPU usage is a difference between two refreshes; the first sample only primes it
+    c.sample();
+    thread::sleep(Duration::from_millis(250));
+    let period = Duration::from_secs_f64(opts.interval.max(0.1));
+    let mut next = Instant::now();
+    loop {
+        let snap = c.sample();
+        {
+            let mut v = viewers.lock().unwrap_or_else(|e| e.into_inner());
+            // a viewer that went away, or fell a whole queue behind, is dropped
+            v.queues.retain(|q| q.try_send(snap.clone()).is_ok());
+            if opts.stdio && v.queues.is_empty() { return Ok(()); }
+            if v.backlog.len() == BACKLOG { v.backlog.pop_front(); }
+            v.backlog.push_back(snap);
+        }
+        next += period;
+        let now = Instant::now();
+        while next < now { next += period; }
+        thread::sleep(next - now);
+    }
+}
+
+fn accept(listener: TcpListener, channels: &[Channel], groups: &[SensorGroup], viewers: &Mutex<Viewers>) {
+    for stream in listener.incoming().flatten() {
+        let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+        let _ = stream.set_write_timeout(Some(Duration::from_secs(5)));
+        let _ = stream.set_nodelay(true);
+        let joined = Recorder::to_writer(Box::new(stream), Format::JsonLines, Path::new(&peer), channels, groups).and_then(|r| join(viewers, r));
+        match joined {
+            Ok(()) => eprintln!("sia agent: viewer {} connected", peer),
+            Err(e) => eprintln!("sia agent: viewer {}: {}", peer, e),
+        }
+    }
+}
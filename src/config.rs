@@ -0,0 +1,136 @@
+//! Startup options: sample rate, history length, initial plot window, window geometry and which collectors
+//! run. They come from `~/.config/sia/config.toml` (or `--config FILE`), optionally a named preset from it,
+//! then the command line, each overriding the one before:
+//!
+//! ```toml
+//! rate = 1.0              # samples per second
+//! history = 900           # seconds of raw samples kept
+//! window = 120            # seconds shown at start
+//! geometry = "1230x1130"  # or "WxH+X+Y"
+//!
+//! [collectors]
+//! nvidia = false
+//!
+//! [preset.soak]
+//! rate = 0.1
+//! history = 86400
+//! ```
+//!
+//! `bench` (10 Hz, 2 min) and `soak` (0.1 Hz, a day) are built in; a preset of the same name in the file
+//! replaces them.
+use serde::Deserialize;
+use sia_core::CollectorConfig;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// `WxH` or `WxH+X+Y`, in logical pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Geometry { pub size: [f32; 2], pub pos: Option<[f32; 2]> }
+
+impl FromStr for Geometry {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        let bad = || format!("{}: expected WxH or WxH+X+Y", s);
+        let mut parts = s.split('+');
+        let (w, h) = parts.next().and_then(|wh| wh.split_once('x')).ok_or_else(bad)?;
+        let num = |v: &str| v.trim().parse::<f32>().ok().filter(|v| v.is_finite() && *v >= 0.0).ok_or_else(bad);
+        let size = [num(w)?, num(h)?];
+        let pos = match (parts.next(), parts.next(), parts.next()) {
+            (None, _, _) => None,
+            (Some(x), Some(y), None) => Some([num(x)?, num(y)?]),
+            _ => return Err(bad()),
+        };
+        Ok(Self { size, pos })
+    }
+}
+
+/// One layer of options, as written in the file (top level or a preset) or given on the command line.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Layer {
+    pub rate: Option<f64>,
+    pub history: Option<f64>,
+    pub window: Option<f64>,
+    pub geometry: Option<String>,
+    pub collectors: BTreeMap<String, bool>,
+}
+
+impl Layer {
+    fn over(self, base: Layer) -> Layer {
+        let mut collectors = base.collectors;
+        collectors.extend(self.collectors);
+        Layer { rate: self.rate.or(base.rate), history: self.history.or(base.history), window: self.window.or(base.window), geometry: self.geometry.or(base.geometry), collectors }
+    }
+}
+
+/// A top-level [`Layer`] plus the presets. Spelled out rather than flattened so unknown keys are still errors.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct File {
+    rate: Option<f64>,
+    history: Option<f64>,
+    window: Option<f64>,
+    geometry: Option<String>,
+    collectors: BTreeMap<String, bool>,
+    preset: BTreeMap<String, Layer>,
+}
+
+fn builtin(name: &str) -> Option<Layer> {
+    match name {
+        "bench" => Some(Layer { rate: Some(10.0), history: Some(120.0), window: Some(60.0), ..Layer::default() }),
+        "soak" => Some(Layer { rate: Some(0.1), history: Some(86400.0), window: Some(3600.0), ..Layer::default() }),
+        _ => None,
+    }
+}
+
+/// The options in effect.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Samples per second.
+    pub rate: f64,
+    /// Seconds of raw samples kept; aggregates reach further back.
+    pub history: f64,
+    /// Plot window at start; `None` keeps the one saved from the last run.
+    pub window: Option<f64>,
+    pub geometry: Geometry,
+    pub collectors: CollectorConfig,
+}
+
+impl Config {
+    /// Reads `path` (or the default config file, which may be missing), applies `preset` and then `cli`.
+    pub fn resolve(path: Option<&Path>, preset: Option<&str>, cli: Layer) -> Result<Self, String> {
+        let default = sia_core::config_dir().map(|d| d.join("config.toml"));
+        let file = match path.map(Path::to_path_buf).or(default) {
+            Some(p) if path.is_some() || p.exists() => read(&p)?,
+            _ => File::default(),
+        };
+        let mut layer = Layer { rate: file.rate, history: file.history, window: file.window, geometry: file.geometry, collectors: file.collectors };
+        if let Some(name) = preset {
+            let names = || file.preset.keys().map(String::as_str).chain(["bench", "soak"]).collect::<Vec<_>>().join(", ");
+            let p = file.preset.get(name).cloned().or_else(|| builtin(name)).ok_or_else(|| format!("{}: no such preset (have {})", name, names()))?;
+            layer = p.over(layer);
+        }
+        let l = cli.over(layer);
+        let rate = l.rate.unwrap_or(1.0);
+        if !(rate > 0.0 && rate <= 20.0) { return Err(format!("rate {}: expected more than 0 and at most 20 samples per second", rate)); }
+        let history = l.history.unwrap_or(900.0);
+        if history.is_nan() || history < 10.0 { return Err(format!("history {}: expected at least 10 seconds", history)); }
+        if let Some(w) = l.window.filter(|w| w.is_nan() || *w < 10.0) { return Err(format!("window {}: expected at least 10 seconds", w)); }
+        let geometry = l.geometry.as_deref().map(str::parse).transpose()?.unwrap_or(Geometry { size: [1230.0, 1130.0], pos: None });
+        let mut collectors = CollectorConfig::default();
+        for (name, on) in &l.collectors { collectors.enable(name, *on)?; }
+        Ok(Self { rate, history, window: l.window, geometry, collectors })
+    }
+
+    /// History length in samples.
+    pub fn capacity(&self) -> usize { (self.history * self.rate).ceil() as usize }
+
+    pub fn period(&self) -> std::time::Duration { std::time::Duration::from_secs_f64(1.0 / self.rate) }
+}
+
+fn read(path: &Path) -> Result<File, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
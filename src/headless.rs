@@ -0,0 +1,100 @@
+//! `sia --headless`: the same sensors, names and groups as the GUI, printed to the terminal. Without
+//! `--once`/`--interval` it redraws a grouped table every second; with them it prints one line per sample.
+use sia_core::mqtt::{MqttConfig, Publisher};
+use crate::config::Config;
+use crate::Outputs;
+use sia_core::{Channel, Collector, Exporter, Kind, Pusher, Recorder, SensorGroup, Sink, SinkThread, Snapshot};
+use std::io::{self, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub struct Options { pub once: bool, pub interval: Option<f64>, pub cfg: Config, pub outputs: Outputs }
+
+pub fn run(opts: &Options) -> io::Result<()> {
+    let mut c = Collector::new(&opts.cfg.collectors);
+    // CPU usage is a difference between two refreshes; the first sample only primes it
+    c.sample();
+    thread::sleep(Duration::from_millis(250));
+    let out = io::stdout();
+    let mut out = out.lock();
+    let o = &opts.outputs;
+    let mut rec = o.record.as_deref().map(|p| Recorder::create(p, c.channels(), c.groups())).transpose()?;
+    let exporter = o.listen.map(|a| Exporter::bind(a, c.channels(), c.groups())).transpose()?;
+    let period = opts.interval.map(|s| Duration::from_secs_f64(s.max(0.1))).unwrap_or_else(|| opts.cfg.period());
+    let mut sinks: Vec<Box<dyn Sink>> = vec![];
+    if let Some(p) = &o.push { sinks.push(Box::new(Pusher::new(p.clone(), c.channels(), c.groups())?)); }
+    if let Some(m) = &o.mqtt { sinks.push(Box::new(Publisher::new(MqttConfig { period, ..m.clone() }, c.channels(), c.groups())?)); }
+    if opts.once {
+        let snap = c.sample();
+        if let Some(r) = rec.as_mut() { r.write(&snap)?; }
+        for s in &mut sinks { s.write(&snap)?; s.flush()?; }
+        return print_line(&mut out, &c, &snap);
+    }
+
+    // a failed push is retried with the next batch, so it is reported once but not fatal
+    let mut reported: Option<String> = None;
+    let pushes = SinkThread::spawn(move |err| {
+        if let Some(e) = err.as_ref().filter(|e| reported.as_ref() != Some(e)) { eprintln!("sia: {}", e); }
+        reported = err;
+    });
+    for s in sinks { pushes.add(s); }
+    let mut next = Instant::now();
+    loop {
+        let snap = c.sample();
+        if let Some(r) = rec.as_mut() { r.write(&snap)?; }
+        if let Some(e) = &exporter { e.update(&snap); }
+        pushes.send(&snap);
+        if opts.interval.is_some() { print_line(&mut out, &c, &snap)?; } else { print_table(&mut out, &c, &snap)?; }
+        out.flush()?;
+        next += period;
+        let now = Instant::now();
+        while next < now { next += period; }
+        thread::sleep(next - now);
+    }
+}
+
+fn value(ch: &Channel, v: Option<f64>) -> String {
+    match (ch.kind, v) {
+        (_, None) => "–".into(),
+        (Kind::Temperature, Some(v)) => format!("{:.1} {}", v, ch.kind.unit()),
+        (_, Some(v)) => format!("{:.0} {}", v, ch.kind.unit()),
+    }
+}
+
+fn state(g: &SensorGroup, v: Option<f64>) -> &'static str {
+    match v { Some(v) if v >= g.hot => "  HOT", Some(v) if v >= g.warn => "  warn", _ => "" }
+}
+
+/// `2025-06-01T12:00:00+02:00 CPU 12 % | RAM 40 % | CPU: Package 45.0 °C | ...`
+fn print_line(out: &mut impl Write, c: &Collector, snap: &Snapshot) -> io::Result<()> {
+    let chs = c.channels();
+    let mut fields: Vec<String> = vec![];
+    for (i, ch) in chs.iter().enumerate().filter(|(_, ch)| ch.kind == Kind::Utilization) { fields.push(format!("{} {}", ch.name, value(ch, snap.values[i]))); }
+    for g in c.groups() {
+        for it in g.items.iter().chain(&g.freqs) { fields.push(format!("{}: {} {}", g.display, it.name, value(&chs[it.idx], snap.values[it.idx]))); }
+    }
+    let t = chrono::DateTime::<chrono::Local>::from(snap.wall).to_rfc3339_opts(chrono::SecondsFormat::Secs, false);
+    writeln!(out, "{} {}", t, fields.join(" | "))
+}
+
+fn print_table(out: &mut impl Write, c: &Collector, snap: &Snapshot) -> io::Result<()> {
+    let chs = c.channels();
+    let width = chs.iter().map(|ch| ch.name.chars().count()).max().unwrap_or(0).max(12);
+    // home + clear, so the table redraws in place
+    write!(out, "\x1b[H\x1b[2J")?;
+    let t = chrono::DateTime::<chrono::Local>::from(snap.wall).format("%H:%M:%S");
+    writeln!(out, "SIA - System Information Analyzer   {}\n", t)?;
+    writeln!(out, "Utilization")?;
+    for (i, ch) in chs.iter().enumerate().filter(|(_, ch)| ch.kind == Kind::Utilization) {
+        writeln!(out, "  {:width$}  {:>10}", ch.name, value(ch, snap.values[i]))?;
+    }
+    for g in c.groups() {
+        writeln!(out, "\n{}  (warn {:.0} / hot {:.0} °C)", g.display, g.warn, g.hot)?;
+        for it in &g.items {
+            let v = snap.values[it.idx];
+            writeln!(out, "  {:width$}  {:>10}{}", it.name, value(&chs[it.idx], v), state(g, v))?;
+        }
+        for it in &g.freqs { writeln!(out, "  {:width$}  {:>10}", it.name, value(&chs[it.idx], snap.values[it.idx]))?; }
+    }
+    Ok(())
+}
@@ -1,481 +1,291 @@
+use clap::Parser;
 use eframe::{egui, egui::Vec2};
 use egui::{Align2, Color32, FontFamily, FontId, RichText, TextStyle};
-use egui_plot::{Corner, Legend, Line, Plot, PlotBounds, PlotPoints, Text};
-use once_cell::sync::Lazy;
-use std::collections::{BTreeMap, VecDeque};
-use std::fs;
-use std::io::Read;
-use std::path::PathBuf;
-use std::time::{Duration, Instant};
-use sysinfo::{CpuExt, System, SystemExt};
-
-// ===================== Optional NVIDIA support =====================
-#[cfg(feature = "nvidia")]
-mod nvgpu {
-    use nvml_wrapper::{
-        enum_wrappers::device::{Clock as NvClock, TemperatureSensor},
-        Nvml,
-    };
-
-    pub struct NvState {
-        pub nvml: Nvml,
-        pub device_index: u32,
-    }
-
-    impl NvState {
-        pub fn try_new() -> Option<Self> {
-            let nvml = Nvml::init().ok()?;
-            let count = nvml.device_count().ok()?;
-            if count == 0 { return None; }
-            let idx = 0u32;
-            let _ = nvml.device_by_index(idx).ok()?; // probe
-            Some(Self { nvml, device_index: idx })
-        }
-    }
-
-    pub fn first_gpu_metrics(state: &NvState) -> Option<(f64, f64, f64)> {
-        let dev = state.nvml.device_by_index(state.device_index).ok()?;
-        let util = dev.utilization_rates().ok()?; // gpu, mem (% u32)
-        let mem = dev.memory_info().ok()?; // bytes
-        let temp = dev.temperature(TemperatureSensor::Gpu).ok()? as f64; // °C
-        let mem_pct = if mem.total > 0 { (mem.used as f64 / mem.total as f64) * 100.0 } else { 0.0 };
-        Some((util.gpu as f64, mem_pct, temp))
+use egui_plot::{Corner, GridMark, HLine, Legend, Line, LineStyle, Plot, PlotBounds, PlotPoint, PlotPoints, PlotUi, Text, VLine};
+use sia_core::taxonomy::{natural_cmp, tint};
+use sia_core::rules::Rules;
+use sia_core::push::{LineFormat, Target};
+use sia_core::{Calibrations, Channel, Collector, CollectorConfig, Endpoint, History, Kind, Lod, MqttConfig, Player, PushConfig, Recording, Remote, Rgb, Sampler, SensorGroup, Series};
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+mod agent;
+mod config;
+mod headless;
+mod settings;
+mod tui;
+
+use config::{Config, Layer};
+use settings::{LegendPlacement, Settings, TimeAxis};
+
+fn c32(c: Rgb) -> Color32 { Color32::from_rgb(c.0, c.1, c.2) }
+
+// ===================== Time axis =====================
+fn clock(t: SystemTime) -> String { chrono::DateTime::<chrono::Local>::from(t).format("%H:%M:%S").to_string() }
+
+/// x is monotonic seconds since start; in clock mode it is shown as local time via the history's wall anchor.
+fn x_axis_fmt(axis: TimeAxis, h: &History) -> impl Fn(GridMark, &RangeInclusive<f64>) -> String {
+    let wall_at_zero = h.wall_at_zero;
+    move |m, _| match axis {
+        TimeAxis::Elapsed => format!("{:.0}", m.value),
+        TimeAxis::Clock => clock(wall_at_zero + Duration::from_secs_f64(m.value.max(0.0))),
     }
+}
 
-    /// Returns clocks in MHz: (graphics, sm, memory, video)
-    pub fn gpu_clocks_mhz(state: &NvState) -> Option<(f64, f64, f64, f64)> {
-        let dev = state.nvml.device_by_index(state.device_index).ok()?;
-        let g = dev.clock_info(NvClock::Graphics).ok()? as f64;
-        let sm = dev.clock_info(NvClock::SM).ok().map(|v| v as f64).unwrap_or(g);
-        let m = dev.clock_info(NvClock::Memory).ok()? as f64;
-        let v = dev.clock_info(NvClock::Video).ok().map(|v| v as f64).unwrap_or(g);
-        Some((g, sm, m, v))
+fn hover_fmt(axis: TimeAxis, h: &History) -> impl Fn(&str, &PlotPoint) -> String {
+    let wall_at_zero = h.wall_at_zero;
+    move |name, p| {
+        let t = match axis { TimeAxis::Elapsed => format!("{:.1} s", p.x), TimeAxis::Clock => clock(wall_at_zero + Duration::from_secs_f64(p.x.max(0.0))) };
+        if name.is_empty() { format!("{}\n{:.2}", t, p.y) } else { format!("{}\n{}\n{:.2}", name, t, p.y) }
     }
 }
 
-// ===================== Time series helpers =====================
-#[derive(Default, Clone)]
-struct RollingSeries {
-    xs: VecDeque<f64>,
-    ys: VecDeque<f64>,
-    cap: usize,
-}
-impl RollingSeries {
-    fn new(cap: usize) -> Self { Self { xs: VecDeque::with_capacity(cap), ys: VecDeque::with_capacity(cap), cap } }
-    fn push(&mut self, x: f64, y: f64) { if self.xs.len() == self.cap { self.xs.pop_front(); self.ys.pop_front(); } self.xs.push_back(x); self.ys.push_back(y); }
-    fn points_after(&self, x_min: f64) -> PlotPoints {
-        let mut out: Vec<[f64; 2]> = Vec::with_capacity(self.xs.len());
-        for (x, y) in self.xs.iter().zip(self.ys.iter()) { if *x >= x_min { out.push([*x, *y]); } }
-        PlotPoints::from(out)
-    }
-    fn points_after_scaled(&self, x_min: f64, div: f64) -> PlotPoints {
-        let mut out: Vec<[f64; 2]> = Vec::with_capacity(self.xs.len());
-        for (x, y) in self.xs.iter().zip(self.ys.iter()) { if *x >= x_min { out.push([*x, *y / div]); } }
-        PlotPoints::from(out)
-    }
-    fn min_max_y(&self, x_min: f64, x_max: f64) -> Option<(f64, f64)> {
-        let mut mn = f64::INFINITY; let mut mx = f64::NEG_INFINITY;
-        for (x, y) in self.xs.iter().zip(self.ys.iter()) {
-            if *x >= x_min && *x <= x_max { if *y < mn { mn = *y; } if *y > mx { mx = *y; } }
-        }
-        if mn.is_finite() && mx.is_finite() { Some((mn, mx)) } else { None }
-    }
-    fn last_y(&self) -> Option<f64> { self.ys.back().copied() }
+fn span(secs: f64) -> String {
+    if secs < 90.0 { format!("{:.0} s", secs) } else if secs < 5400.0 { format!("{:.0} min", secs / 60.0) } else { format!("{:.1} h", secs / 3600.0) }
 }
 
-// ===================== Sensors discovery =====================
-#[derive(Clone, Debug)]
-struct TempSensor { raw_name: String, raw_label: String, path: PathBuf }
-static HWMON_SENSORS: Lazy<Vec<TempSensor>> = Lazy::new(discover_hwmon_temps);
-
-#[derive(Clone, Debug)]
-struct FreqSensor { core: usize, path: PathBuf }
-static FREQ_SENSORS: Lazy<Vec<FreqSensor>> = Lazy::new(discover_cpu_freqs);
-
-fn discover_cpu_freqs() -> Vec<FreqSensor> {
-    let mut sensors = vec![];
-    if let Ok(entries) = fs::read_dir("/sys/devices/system/cpu") {
-        for e in entries.flatten() {
-            let p = e.path();
-            let name = p.file_name().and_then(|s| s.to_str()).unwrap_or("");
-            if !name.starts_with("cpu") { continue; }
-            let idx = match name.trim_start_matches("cpu").parse::<usize>() { Ok(v)=>v, Err(_)=>continue };
-            let cf = p.join("cpufreq");
-            let cand1 = cf.join("scaling_cur_freq");
-            let cand2 = cf.join("cpuinfo_cur_freq");
-            let path = if cand1.exists() { cand1 } else if cand2.exists() { cand2 } else { continue };
-            sensors.push(FreqSensor { core: idx, path });
-        }
+/// Marks where the machine resumed from suspend; the series themselves already break there.
+fn suspend_markers(plot_ui: &mut PlotUi, h: &History, x_min: f64, y_top: f64) {
+    for s in h.suspends.iter().filter(|s| s.x >= x_min) {
+        plot_ui.vline(VLine::new(s.x).color(Color32::GRAY).style(LineStyle::dashed_dense()));
+        plot_ui.text(Text::new([s.x, y_top].into(), format!(" suspended for {}", span(s.secs))).color(Color32::GRAY).anchor(Align2::LEFT_TOP));
     }
-    sensors.sort_by_key(|s| s.core);
-    sensors
 }
 
-fn read_freq_khz(path: &PathBuf) -> Option<f64> { let mut s=String::new(); fs::File::open(path).ok()?.read_to_string(&mut s).ok()?; s.trim().parse::<f64>().ok() }
-
-fn discover_hwmon_temps() -> Vec<TempSensor> {
-    let mut sensors = vec![];
-    if let Ok(entries) = fs::read_dir("/sys/class/hwmon") {
-        for e in entries.flatten() {
-            let base = e.path();
-            let name = fs::read_to_string(base.join("name")).unwrap_or_default().trim().to_string();
-            if let Ok(files) = fs::read_dir(&base) {
-                for f in files.flatten() {
-                    let p = f.path(); let fname = p.file_name().and_then(|s| s.to_str()).unwrap_or("");
-                    if fname.starts_with("temp") && fname.ends_with("_input") {
-                        let mut label = name.clone();
-                        let label_path = base.join(fname.replace("_input","_label"));
-                        if let Ok(lbl) = fs::read_to_string(label_path) { let l=lbl.trim(); if !l.is_empty() { label = l.to_string(); } }
-                        sensors.push(TempSensor { raw_name: name.clone(), raw_label: label, path: p.clone() });
-                    }
-                }
-            }
-        }
+/// One line per run of samples, so late or missing samples show up as a hole. Where the window reaches past
+/// the raw samples the line is the bucket averages, with the bucket minima and maxima drawn faintly around it.
+/// Lines are decimated to the plot's width.
+fn plot_series(plot_ui: &mut PlotUi, s: Series, (x_min, x_max): (f64, f64), div: f64, max_gap: f64, name: &str, color: Color32) {
+    let lod = Lod { x_max, px: plot_ui.response().rect.width() as usize };
+    for segs in s.extremes_lod(x_min, div, max_gap, lod).into_iter().flatten() {
+        for seg in segs.iter() { plot_ui.line(Line::new(seg.iter().copied().collect::<PlotPoints>()).color(color.gamma_multiply(0.35))); }
     }
-    sensors
+    for seg in s.segments_lod(x_min, div, max_gap, lod).iter() { plot_ui.line(Line::new(seg.iter().copied().collect::<PlotPoints>()).name(name).color(color)); }
 }
 
-fn read_temp_c(path: &PathBuf) -> Option<f64> { let mut s=String::new(); fs::File::open(path).ok()?.read_to_string(&mut s).ok()?; let v: f64 = s.trim().parse().ok()?; Some(if v>1000.0 { v/1000.0 } else { v }) }
-
-// ===================== Grouping & taxonomy =====================
-#[derive(Clone, Debug)]
-struct SensorItem { name: String, idx: usize, visible: bool, color: Color32 }
-#[derive(Clone, Debug)]
-struct SensorGroup { key: String, display: String, items: Vec<SensorItem>, visible: bool, warn: f64, hot: f64, show_thresholds: bool }
-
-fn classify(raw: &str) -> (String, String, f64, f64) {
-    let r = raw.to_lowercase();
-    if r.contains("coretemp") || r.contains("k10temp") || r.contains("zen") || r.contains("cpu") { return ("cpu".into(), "CPU".into(), 90.0, 100.0); }
-    if r.contains("amdgpu") { return ("gpu".into(), "GPU (amdgpu)".into(), 85.0, 95.0); }
-    if r.contains("nvidia") || r.contains("gpu") { return ("gpu".into(), "GPU (nvidia)".into(), 85.0, 95.0); }
-    if r.contains("nvme") { return ("nvme".into(), "NVMe SSD".into(), 70.0, 80.0); }
-    if r.contains("spd") { return ("ramspd".into(), "Memory (SPD Hub)".into(), 70.0, 85.0); }
-    if r.contains("iwlwifi") { return ("wifi".into(), "Wi‑Fi Controller (iwlwifi)".into(), 80.0, 90.0); }
-    if r.contains("r8169") { return ("eth".into(), "Ethernet Controller (r8169)".into(), 80.0, 90.0); }
-    if r.contains("igc") { return ("eth".into(), "Ethernet Controller (igc)".into(), 80.0, 90.0); }
-    if r.contains("e1000") { return ("eth".into(), "Ethernet Controller (e1000)".into(), 80.0, 90.0); }
-    if r.contains("r8125") { return ("eth".into(), "Ethernet Controller (r8125)".into(), 80.0, 90.0); }
-    if r.contains("acpitz") { return ("acpi".into(), "System Temperature (acpitz)".into(), 80.0, 95.0); }
-    if r.contains("pch") || r.contains("isa") { return ("chipset".into(), "Chipset".into(), 85.0, 95.0); }
-    (raw.to_string(), raw.to_string(), 90.0, 100.0)
-}
+// ===================== App model =====================
+/// Where the plotted history comes from: this machine's sensors, a recording played back (`--replay`) or
+/// an agent on another machine (`--connect`).
+enum Source { Live(Sampler), Replay(Player), Remote(Remote) }
 
-fn nice_label(group: &str, raw: &str) -> String {
-    let ll = raw.to_lowercase();
-    match group {
-        "CPU" => { if ll.contains("package") { "CPU (Package)".into() } else if ll.contains("tctl") || ll.contains("tdie") { "CPU (Composite)".into() } else if ll.starts_with("core ") { raw.replace("Core ", "CPU (Core ") + ")" } else { raw.into() } }
-        "GPU (amdgpu)" | "GPU (nvidia)" => { if ll.contains("edge") { "GPU (Edge)".into() } else if ll.contains("hotspot") { "GPU (Hotspot)".into() } else { raw.into() } }
-        "NVMe SSD" => raw.replace("Composite","SSD"),
-        _ => raw.into(),
+impl Source {
+    fn history(&self) -> std::sync::Arc<std::sync::Mutex<History>> {
+        match self { Source::Live(s) => s.history(), Source::Replay(p) => p.history(), Source::Remote(r) => r.history() }
     }
 }
 
-fn palette() -> Vec<Color32> { vec![ Color32::from_rgb(244,67,54), Color32::from_rgb(33,150,243), Color32::from_rgb(76,175,80), Color32::from_rgb(255,152,0), Color32::from_rgb(156,39,176), Color32::from_rgb(121,85,72), Color32::from_rgb(63,81,181), Color32::from_rgb(0,150,136), Color32::from_rgb(205,220,57), Color32::from_rgb(233,30,99), Color32::from_rgb(158,158,158), Color32::from_rgb(255,87,34), Color32::from_rgb(3,169,244), Color32::from_rgb(139,195,74), Color32::from_rgb(171,71,188), Color32::from_rgb(255,238,88), Color32::from_rgb(38,198,218), Color32::from_rgb(141,110,99), Color32::from_rgb(120,144,156) ] }
-
-fn theme_color(kind: &str) -> Color32 {
-    match kind {
-        "cpu" => Color32::from_rgb(244,67,54),     // red
-        "gpu" => Color32::from_rgb(33,150,243),    // blue
-        "nvme" => Color32::from_rgb(255,152,0),    // orange
-        "ramspd" => Color32::from_rgb(76,175,80),  // green
-        "wifi" => Color32::from_rgb(0,150,136),    // teal
-        "eth" => Color32::from_rgb(171,71,188),    // purple
-        _ => Color32::from_rgb(158,158,158),        // grey
-    }
-}
-fn tint(c: Color32, factor: f32) -> Color32 {
-    let (r,g,b,a) = (c.r() as f32, c.g() as f32, c.b() as f32, c.a());
-    let t = |v: f32| -> u8 { v.min(255.0).max(0.0) as u8 };
-    Color32::from_rgba_unmultiplied(t(r + (255.0-r)*factor), t(g + (255.0-g)*factor), t(b + (255.0-b)*factor), a)
+/// One machine on screen: its sensors, their groups and visibility, and where its samples come from.
+struct Host {
+    name: String,
+    channels: Vec<Channel>,
+    // one series per channel: % / °C (after sensors.conf compute and calibration) / MHz
+    source: Source,
+    util: Vec<usize>,                      // utilization channels, in plot order
+    cpu_util: Option<usize>,
+    ram_util: Option<usize>,
+    groups: Vec<SensorGroup>,
 }
 
-fn build_groups() -> Vec<SensorGroup> {
-    let mut map: BTreeMap<String, SensorGroup> = BTreeMap::new();
-    for (idx, s) in HWMON_SENSORS.iter().enumerate() {
-        let (key, display, warn, hot) = classify(&s.raw_name);
-        let entry = map.entry(key.clone()).or_insert(SensorGroup {
-            key: key.clone(),
-            display: display.clone(),
-            items: vec![],
-            visible: true,
-            warn,
-            hot,
-            show_thresholds: false,
-        });
-        let label = nice_label(&display, &s.raw_label);
-        entry.items.push(SensorItem { name: label, idx, visible: false, color: Color32::WHITE });
+impl Host {
+    fn new(name: String, channels: Vec<Channel>, groups: Vec<SensorGroup>, source: Source) -> Self {
+        let mut host = Self { name, util: vec![], cpu_util: None, ram_util: None, source, channels: vec![], groups: vec![] };
+        host.set_sensors(channels, groups);
+        host
     }
 
-    // defaults: prefer composite/package/system/wifi/ethernet
-    for g in map.values_mut() {
-        let mut showed = false;
-        for it in &mut g.items {
-            let n = it.name.to_lowercase();
-            let is_composite = n.contains("composite") || n.contains("package") || n.contains("core)");
-            if !showed && (is_composite || g.display.contains("Wi‑Fi Controller") || g.display.contains("Ethernet Controller") || g.display.contains("System Temperature")) {
-                it.visible = true;
-                showed = true;
-            }
-        }
-        if !showed { if let Some(first) = g.items.first_mut() { first.visible = true; } }
+    fn set_sensors(&mut self, channels: Vec<Channel>, groups: Vec<SensorGroup>) {
+        self.util = channels.iter().enumerate().filter(|(_, c)| c.kind == Kind::Utilization).map(|(i, _)| i).collect();
+        let find = |id: &str| channels.iter().position(|c| c.id == id);
+        (self.cpu_util, self.ram_util) = (find("cpu/util"), find("ram/util"));
+        (self.channels, self.groups) = (channels, groups);
     }
 
-    // assign colors per group with gentle tints so the same thing stays the same color across plots
-    for g in map.values_mut() {
-        let base = theme_color(&g.key);
-        for (i, it) in g.items.iter_mut().enumerate() {
-            it.color = tint(base, (i as f32) * 0.08);
-        }
+    /// A remote host has no name or sensors until its agent is first reached.
+    fn adopt_agent(&mut self) {
+        let Source::Remote(r) = &self.source else { return };
+        if !self.channels.is_empty() { return; }
+        let Some(a) = r.agent() else { return };
+        self.name = a.host;
+        self.set_sensors(a.channels, a.groups);
     }
 
-    // sort items within groups
-    for g in map.values_mut() {
-        if g.display.starts_with("CPU") {
-            g.items.sort_by(|a, b| {
-                fn key(name: &str) -> (u8, i32, String) {
-                    let ln = name.to_lowercase();
-                    let mut tier: u8 = 3;
-                    if ln.contains("package") || ln.contains("composite") { tier = 0; }
-                    else if ln.contains("cpu (core ") { tier = 1; }
-                    let mut idx: i32 = i32::MAX;
-                    if let Some(start) = ln.find("cpu (core ") {
-                        if let Some(end) = ln[start+11..].find(')') {
-                            let num = &ln[start+11..start+11+end];
-                            idx = num.parse::<i32>().unwrap_or(i32::MAX);
-                        }
-                    }
-                    (tier, idx, ln)
-                }
-                key(&a.name).cmp(&key(&b.name))
-            });
-        } else if g.display.starts_with("GPU") {
-            g.items.sort_by(|a, b| {
-                fn tier(name: &str) -> u8 {
-                    let n = name.to_lowercase();
-                    if n.contains("edge") { 0 } else if n.contains("hotspot") { 1 } else { 2 }
-                }
-                tier(&a.name).cmp(&tier(&b.name)).then(a.name.cmp(&b.name))
-            });
-        } else {
-            g.items.sort_by(|a, b| a.name.cmp(&b.name));
-        }
-    }
-
-    // collect & order groups: CPU, GPU, NVMe SSD, Memory (SPD), Wi‑Fi, Ethernet, others
-    let mut v: Vec<_> = map.into_values().collect();
-    fn rank(key: &str) -> i32 { match key { "cpu"=>0, "gpu"=>1, "nvme"=>2, "ramspd"=>3, "wifi"=>4, "eth"=>5, _=>6 } }
-    v.sort_by_key(|g| rank(&g.key));
-    v
+    fn last(h: &History, idx: Option<usize>) -> Option<f64> { idx.and_then(|i| h.series(i).last_y()) }
 }
 
-// ===================== App model =====================
-#[derive(Clone, Copy, PartialEq, Eq)]
-enum LegendPlacement { Footer, Side }
+const REPLAY_SPEEDS: [f64; 7] = [0.5, 1.0, 2.0, 5.0, 10.0, 30.0, 60.0];
 
-struct App {
-    // meta
-    start: Instant,
-    sys: System,
+/// While the window is unfocused, new samples are drawn at most this often.
+const UNFOCUSED_REPAINT: Duration = Duration::from_secs(2);
 
-    // utilization series
-    cpu_util: RollingSeries,
-    ram_util: RollingSeries,
-    gpu_util: RollingSeries,
-    vram_util: RollingSeries,
+/// sia's own CPU use (100% = one core) and resident memory, averaged over at least a second.
+struct Overhead { at: Instant, cpu: Duration, pct: Option<f64>, rss: Option<u64> }
 
-    // temps & freq
-    temp_series: Vec<RollingSeries>,
-    freq_series: Vec<RollingSeries>,       // CPU core kHz
-    freq_visible: Vec<bool>,
-    freq_colors: Vec<Color32>,
+impl Overhead {
+    fn new() -> Self { Self { at: Instant::now(), cpu: sia_core::clock::process_cpu(), pct: None, rss: sia_core::self_rss() } }
 
-    // sensor groups
-    groups: Vec<SensorGroup>,
+    fn refresh(&mut self) {
+        let dt = self.at.elapsed();
+        if dt < Duration::from_secs(1) { return; }
+        let cpu = sia_core::clock::process_cpu();
+        self.pct = Some(cpu.saturating_sub(self.cpu).as_secs_f64() / dt.as_secs_f64() * 100.0);
+        (self.at, self.cpu, self.rss) = (Instant::now(), cpu, sia_core::self_rss());
+    }
+}
 
-    // sampling
-    seconds: f64,
-    sample_period: Duration,
-    last_tick: Instant,
+struct App {
+    // meta
+    start: Instant,
+    start_wall: SystemTime,
+    last_frame: Instant,
+    overhead: Overhead,
+
+    // this machine, a replayed recording, or one host per agent; `host` is the one shown, unless the fleet
+    // dashboard is
+    hosts: Vec<Host>,
+    host: usize,
+    dashboard: bool,
+    compare: Option<(String, String)>,     // (group kind, sensor name) plotted across hosts
+    seek_to: Option<f64>,                  // replay position while its slider is dragged
+    calib: Calibrations,                   // editor copy; edits are sent to the sampler
+    calib_status: String,
+    record_path: String,
+    record_status: String,
 
     // UI state
     display_window_secs: f64,
+    max_window_secs: f64,                  // how far back the history reaches
     legend_place: LegendPlacement,
+    time_axis: TimeAxis,
     ui_font_size: f32,
     ui_font_color: Color32,
     pending_ui_font_size: f32,
     pending_ui_font_color: Color32,
     live_font_preview: bool,
-
-    // NVIDIA (optional)
-    #[cfg(feature = "nvidia")]
-    nv: Option<nvgpu::NvState>,
-    #[cfg(feature = "nvidia")]
-    gpu_temp_idx: Option<usize>,
-    #[cfg(feature = "nvidia")]
-    gpu_clk_graphics: RollingSeries,   // MHz
-    #[cfg(feature = "nvidia")]
-    gpu_clk_sm: RollingSeries,         // MHz
-    #[cfg(feature = "nvidia")]
-    gpu_clk_mem: RollingSeries,        // MHz
-    #[cfg(feature = "nvidia")]
-    gpu_clk_video: RollingSeries,      // MHz
-    #[cfg(feature = "nvidia")]
-    gpu_freq_graphics_vis: bool,
-    #[cfg(feature = "nvidia")]
-    gpu_freq_sm_vis: bool,
-    #[cfg(feature = "nvidia")]
-    gpu_freq_mem_vis: bool,
-    #[cfg(feature = "nvidia")]
-    gpu_freq_video_vis: bool,
-    #[cfg(feature = "nvidia")]
     gpu_mem_effective: bool,
+    settings: Settings,                    // as loaded; written back on exit
 }
 
 impl App {
-    fn new(capacity_secs: usize, sample_hz: f64) -> Self {
-        let mut sys = System::new_all();
-        sys.refresh_all();
-
-        let groups = build_groups();
-        let temp_series = HWMON_SENSORS.iter().map(|_| RollingSeries::new(capacity_secs)).collect::<Vec<_>>();
-        let freq_series = FREQ_SENSORS.iter().map(|_| RollingSeries::new(capacity_secs)).collect::<Vec<_>>();
-        let freq_visible = FREQ_SENSORS.iter().map(|_| true).collect::<Vec<_>>();
-        let pal = palette();
-        let mut freq_colors = Vec::with_capacity(FREQ_SENSORS.len());
-        for i in 0..FREQ_SENSORS.len() { freq_colors.push(pal[i % pal.len()]); }
-
-        #[cfg(feature = "nvidia")]
-        let nv_opt = nvgpu::NvState::try_new();
-        #[cfg(feature = "nvidia")]
-        let (mut temp_series, mut groups, gpu_temp_idx_opt) = {
-            let mut temp_series = temp_series;
-            let mut groups = groups;
-            let idx = temp_series.len();
-            temp_series.push(RollingSeries::new(capacity_secs));
-            // Ensure GPU group exists and add synthetic GPU temp line
-            if let Some(g) = groups.iter_mut().find(|g| g.display.starts_with("GPU")) {
-                g.items.push(SensorItem { name: "GPU (Core)".into(), idx, visible: true, color: Color32::WHITE });
-            } else {
-                let mut g = SensorGroup { key: "gpu".into(), display: "GPU".into(), items: vec![], visible: true, warn: 85.0, hot: 95.0, show_thresholds: false };
-                g.items.push(SensorItem { name: "GPU (Core)".into(), idx, visible: true, color: Color32::WHITE });
-                groups.push(g);
-            }
-            // keep GPU sorted after CPU
-            fn rank(key: &str) -> i32 { match key { "cpu"=>0, "gpu"=>1, "nvme"=>2, "ramspd"=>3, "wifi"=>4, "eth"=>5, _=>6 } }
-            groups.sort_by_key(|g| rank(&g.key));
-            (temp_series, groups, Some(idx))
-        };
-        #[cfg(not(feature = "nvidia"))]
-        let (nv_opt, gpu_temp_idx_opt) = (None::<()> as Option<()>, None::<usize>);
+    /// Starts `outputs` on the sampler; a recording that cannot start is shown in the window rather than fatal.
+    fn new(cfg: &Config, outputs: Outputs) -> std::io::Result<Self> {
+        let collector = Collector::new(&cfg.collectors);
+        let calib = Calibrations::load(cfg.collectors.calibration_file.clone());
+        let channels = collector.channels().to_vec();
+        let groups = collector.groups().to_vec();
+        let sampler = Sampler::spawn(collector, cfg.period(), cfg.capacity());
+        let record_status = match &outputs.record { Some(p) => sampler.start_recording(p).err().map(|e| format!("Not recording: {}", e)).unwrap_or_default(), None => String::new() };
+        outputs.start(&sampler)?;
+        let mut app = Self::with(vec![Host::new(sia_core::hostname(), channels, groups, Source::Live(sampler))], calib, cfg);
+        app.record_path = outputs.record.unwrap_or_else(default_record_path).to_string_lossy().into_owned();
+        app.record_status = record_status;
+        Ok(app)
+    }
 
-        Self {
+    /// Plays `rec` back (`--replay FILE`), keeping up to the configured history's worth of rows on screen.
+    fn replay(rec: Recording, cfg: &Config) -> Self {
+        let (channels, groups) = (rec.channels.clone(), rec.groups.clone());
+        let name = rec.host.clone().unwrap_or_else(|| rec.path.display().to_string());
+        let mut player = Player::new(rec, cfg.capacity());
+        player.playing = true;
+        Self::with(vec![Host::new(name, channels, groups, Source::Replay(player))], Calibrations::default(), cfg)
+    }
+
+    /// One host per agent (`--connect`), each shown offline until it is reached.
+    fn remote(remotes: Vec<Remote>, cfg: &Config) -> Self {
+        let hosts = remotes.into_iter().map(|r| Host::new(r.endpoint().to_string(), vec![], vec![], Source::Remote(r))).collect();
+        Self::with(hosts, Calibrations::default(), cfg)
+    }
+
+    /// Opens with the view saved by the last run, unless `cfg` sets the plot window.
+    fn with(hosts: Vec<Host>, calib: Calibrations, cfg: &Config) -> Self {
+        let mut app = Self {
             start: Instant::now(),
-            sys,
-            cpu_util: RollingSeries::new(capacity_secs),
-            ram_util: RollingSeries::new(capacity_secs),
-            gpu_util: RollingSeries::new(capacity_secs),
-            vram_util: RollingSeries::new(capacity_secs),
-            temp_series,
-            freq_series,
-            freq_visible,
-            freq_colors,
-            groups,
-            seconds: 0.0,
-            sample_period: Duration::from_secs_f64((1.0 / sample_hz).max(0.05)),
-            last_tick: Instant::now(),
+            start_wall: SystemTime::now(),
+            last_frame: Instant::now(),
+            overhead: Overhead::new(),
+            dashboard: hosts.len() > 1,
+            compare: None,
+            seek_to: None,
+            hosts,
+            host: 0,
+            calib,
+            calib_status: String::new(),
+            record_path: default_record_path().to_string_lossy().into_owned(),
+            record_status: String::new(),
             display_window_secs: 120.0,
+            max_window_secs: cfg.history.max(sia_core::series::TIERED_SPAN),
             legend_place: LegendPlacement::Footer,
+            time_axis: TimeAxis::Elapsed,
             ui_font_size: 14.0,
             ui_font_color: Color32::WHITE,
             pending_ui_font_size: 14.0,
             pending_ui_font_color: Color32::WHITE,
             live_font_preview: false,
-            #[cfg(feature = "nvidia")]
-            nv: nv_opt,
-            #[cfg(feature = "nvidia")]
-            gpu_temp_idx: gpu_temp_idx_opt,
-            #[cfg(feature = "nvidia")]
-            gpu_clk_graphics: RollingSeries::new(capacity_secs),
-            #[cfg(feature = "nvidia")]
-            gpu_clk_sm: RollingSeries::new(capacity_secs),
-            #[cfg(feature = "nvidia")]
-            gpu_clk_mem: RollingSeries::new(capacity_secs),
-            #[cfg(feature = "nvidia")]
-            gpu_clk_video: RollingSeries::new(capacity_secs),
-            #[cfg(feature = "nvidia")]
-            gpu_freq_graphics_vis: true,
-            #[cfg(feature = "nvidia")]
-            gpu_freq_sm_vis: true,
-            #[cfg(feature = "nvidia")]
-            gpu_freq_mem_vis: true,
-            #[cfg(feature = "nvidia")]
-            gpu_freq_video_vis: false,
-            #[cfg(feature = "nvidia")]
             gpu_mem_effective: false,
-        }
+            settings: Settings::load(),
+        };
+        app.apply_settings();
+        if let Some(w) = cfg.window { app.display_window_secs = w; }
+        app.display_window_secs = app.display_window_secs.clamp(10.0, app.max_window_secs);
+        app
     }
 
-    fn sample(&mut self) {
-        // refresh
-        self.sys.refresh_cpu();
-        self.sys.refresh_memory();
-
-        // CPU / RAM %
-        let avg_cpu: f32 = self.sys.cpus().iter().map(|c| c.cpu_usage()).sum::<f32>() / (self.sys.cpus().len().max(1) as f32);
-        let cpu_pct = avg_cpu as f64;
-        let total = self.sys.total_memory() as f64;
-        let used = self.sys.used_memory() as f64;
-        let ram_pct = if total>0.0 { (used/total)*100.0 } else { 0.0 };
-
-        // timebase
-        self.seconds += self.sample_period.as_secs_f64();
-
-        // push util
-        self.cpu_util.push(self.seconds, cpu_pct);
-        self.ram_util.push(self.seconds, ram_pct);
-
-        // NVIDIA sampling
-        #[cfg(feature = "nvidia")]
-        {
-            if let Some(nv) = &self.nv {
-                if let Some((gpu_pct, vram_pct, temp_c)) = nvgpu::first_gpu_metrics(nv) {
-                    self.gpu_util.push(self.seconds, gpu_pct);
-                    self.vram_util.push(self.seconds, vram_pct);
-                    if let Some(idx) = self.gpu_temp_idx { self.temp_series[idx].push(self.seconds, temp_c); }
-                } else {
-                    self.gpu_util.push(self.seconds, f64::NAN);
-                    self.vram_util.push(self.seconds, f64::NAN);
-                }
-                if let Some((g, sm, m, v)) = nvgpu::gpu_clocks_mhz(nv) {
-                    self.gpu_clk_graphics.push(self.seconds, g);
-                    self.gpu_clk_sm.push(self.seconds, sm);
-                    self.gpu_clk_mem.push(self.seconds, m);
-                    self.gpu_clk_video.push(self.seconds, v);
-                }
-            } else {
-                self.gpu_util.push(self.seconds, f64::NAN);
-                self.vram_util.push(self.seconds, f64::NAN);
-            }
-        }
-        #[cfg(not(feature = "nvidia"))]
-        {
-            self.gpu_util.push(self.seconds, f64::NAN);
-            self.vram_util.push(self.seconds, f64::NAN);
+    fn apply_settings(&mut self) {
+        let s = &self.settings;
+        self.ui_font_size = s.font_size;
+        self.ui_font_color = Color32::from_rgba_unmultiplied(s.font_color[0], s.font_color[1], s.font_color[2], s.font_color[3]);
+        (self.pending_ui_font_size, self.pending_ui_font_color) = (self.ui_font_size, self.ui_font_color);
+        self.display_window_secs = s.window_secs;
+        self.legend_place = s.legend;
+        self.time_axis = s.time_axis;
+        self.gpu_mem_effective = s.gpu_mem_effective;
+        // sensor ids and group keys describe this machine; replayed and remote hosts keep their defaults
+        for g in self.hosts.iter_mut().filter(|h| matches!(h.source, Source::Live(_))).flat_map(|h| &mut h.groups) {
+            if let Some(v) = s.thresholds.get(&g.key) { g.show_thresholds = *v; }
+            for it in g.items.iter_mut().chain(&mut g.freqs) { if let Some(v) = s.sensors.get(&it.id) { it.visible = *v; } }
         }
+    }
 
-        // CPU per-core frequencies (kHz)
-        for (i, fsens) in FREQ_SENSORS.iter().enumerate() {
-            if let Some(khz) = read_freq_khz(&fsens.path) { self.freq_series[i].push(self.seconds, khz); }
+    /// The current view folded into the loaded settings, so sensors absent this run keep their entries.
+    /// Sensor visibility is only taken from the live local sampler, never from a replay or a remote host.
+    fn save_settings(&mut self) {
+        let s = &mut self.settings;
+        s.font_size = self.ui_font_size;
+        s.font_color = self.ui_font_color.to_srgba_unmultiplied();
+        s.window_secs = self.display_window_secs;
+        s.legend = self.legend_place;
+        s.time_axis = self.time_axis;
+        s.gpu_mem_effective = self.gpu_mem_effective;
+        for g in self.hosts.iter().filter(|h| matches!(h.source, Source::Live(_))).flat_map(|h| &h.groups) {
+            s.thresholds.insert(g.key.clone(), g.show_thresholds);
+            for it in g.items.iter().chain(&g.freqs) { s.sensors.insert(it.id.clone(), it.visible); }
         }
-        // Temperatures
-        for (i, ts) in HWMON_SENSORS.iter().enumerate() {
-            if let Some(t) = read_temp_c(&ts.path) { self.temp_series[i].push(self.seconds, t); }
+        if let Err(e) = s.save() { eprintln!("sia: not saving settings: {}", e); }
+    }
+
+    /// Repaints when a host gets a new sample, instead of on a timer; input repaints by itself.
+    fn repaint_on_samples(&self, ctx: &egui::Context) {
+        for host in &self.hosts {
+            let ctx = ctx.clone();
+            host.source.history().lock().unwrap_or_else(|e| e.into_inner()).on_sample = Some(Box::new(move || {
+                if ctx.input(|i| i.focused) { ctx.request_repaint() } else { ctx.request_repaint_after(UNFOCUSED_REPAINT) }
+            }));
         }
     }
 }
 
+/// `~/sia-<date>-<time>.csv`
+fn default_record_path() -> PathBuf {
+    let name = format!("sia-{}.csv", chrono::Local::now().format("%Y%m%d-%H%M%S"));
+    std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default().join(name)
+}
+
 // ===================== UI =====================
 impl eframe::App for App {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) { self.save_settings(); }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Typography
         let mut style: egui::Style = (*ctx.style()).clone();
@@ -488,32 +298,120 @@ impl eframe::App for App {
             (TextStyle::Small,    FontId::new(self.ui_font_size, FontFamily::Proportional)),
         ].into();
         ctx.set_style(style);
+        self.overhead.refresh();
+        for host in &mut self.hosts { host.adopt_agent(); }
+
+        if self.hosts.len() > 1 {
+            egui::TopBottomPanel::top("hosts").show(ctx, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label(RichText::new("Hosts:").strong());
+                    ui.selectable_value(&mut self.dashboard, true, "Dashboard");
+                    for (i, host) in self.hosts.iter().enumerate() {
+                        let down = matches!(&host.source, Source::Remote(r) if r.status().is_some());
+                        let name = if down { RichText::new(&host.name).color(Color32::RED) } else { RichText::new(&host.name) };
+                        if ui.selectable_label(!self.dashboard && self.host == i, name).clicked() { self.host = i; self.dashboard = false; }
+                    }
+                });
+            });
+        }
+        if self.dashboard {
+            self.dashboard(ctx);
+            return;
+        }
+        let host = &mut self.hosts[self.host];
+
+        // replay: move the cursor before taking the history lock, since seeking refills the history
+        let dt = self.last_frame.elapsed();
+        self.last_frame = Instant::now();
+        if let Source::Replay(player) = &mut host.source {
+            player.advance(dt);
+            // rows come from the cursor moving, not from a thread, so keep drawing while playing
+            if player.playing { ctx.request_repaint_after(Duration::from_secs_f64((player.lock().period / player.speed).clamp(1.0 / 60.0, 1.0))); }
+            egui::TopBottomPanel::top("replay").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let name = player.recording().path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                    ui.label(RichText::new(format!("Replay: {}", name)).strong()).on_hover_text(player.recording().path.display().to_string());
+                    ui.separator();
+                    let at_end = player.cursor() >= player.duration();
+                    if ui.button(if player.playing { "⏸ Pause" } else { "▶ Play" }).clicked() {
+                        if !player.playing && at_end { player.seek(0.0); }
+                        player.playing = !player.playing;
+                    }
+                    let mut t = player.cursor();
+                    let dur = player.duration();
+                    if let Some(to) = self.seek_to { t = to; }
+                    // seeking back rebuilds the history, so while dragging only the slider moves
+                    let r = ui.add(egui::Slider::new(&mut t, 0.0..=dur.max(f64::EPSILON)).show_value(false));
+                    if r.dragged() { self.seek_to = Some(t); }
+                    if r.drag_stopped() || (r.changed() && !r.dragged()) { player.seek(t); self.seek_to = None; }
+                    let mmss = |t: f64| format!("{}:{:02}", t as u64 / 60, t as u64 % 60);
+                    ui.label(format!("{} / {}", mmss(t), mmss(dur)));
+                    ui.separator();
+                    egui::ComboBox::from_label("Speed").selected_text(format!("{}×", player.speed)).show_ui(ui, |ui| {
+                        for s in REPLAY_SPEEDS { ui.selectable_value(&mut player.speed, s, format!("{}×", s)); }
+                    });
+                });
+            });
+        }
 
-        // sampling
-        if self.last_tick.elapsed() >= self.sample_period { self.sample(); self.last_tick = Instant::now(); }
-        ctx.request_repaint_after(Duration::from_millis(16));
+        // sampling happens on the sampler thread; hold the history only while drawing
+        let history = host.source.history();
+        let h = history.lock().unwrap_or_else(|e| e.into_inner());
 
         egui::TopBottomPanel::top("top").show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.heading("SIA - System Information Analyzer - © David Crawley 2025");
                 ui.separator();
-                ui.label(format!("Uptime: {}s", self.start.elapsed().as_secs()));
+                match &host.source {
+                    Source::Live(_) => { ui.label(format!("Uptime: {}s", self.start.elapsed().as_secs())); ui.separator(); }
+                    Source::Remote(r) => {
+                        ui.label(format!("Host: {}", host.name)).on_hover_text(r.endpoint().to_string());
+                        if let Some(e) = r.status() { ui.colored_label(Color32::RED, e); }
+                        ui.separator();
+                    }
+                    Source::Replay(_) => {}
+                }
+                ui.label(format!("Samples: {}", h.samples));
+                if h.late > 0 { ui.label(format!("(late: {})", h.late)).on_hover_text("Samples taken more than half a period after their scheduled time"); }
+                ui.separator();
+                ui.label(format!("Last sample: {}", clock(h.wall_at(h.now))));
                 ui.separator();
-                ui.label(format!("Samples: {}", (self.seconds / self.sample_period.as_secs_f64()) as usize));
+                ui.label(format!("CPU: {:.0}%", Host::last(&h, host.cpu_util).unwrap_or(0.0)));
                 ui.separator();
-                ui.label(format!("CPU: {:.0}%", self.cpu_util.last_y().unwrap_or(0.0)));
+                ui.label(format!("RAM: {:.0}%", Host::last(&h, host.ram_util).unwrap_or(0.0)));
                 ui.separator();
-                ui.label(format!("RAM: {:.0}%", self.ram_util.last_y().unwrap_or(0.0)));
+                let pct = self.overhead.pct.map(|p| format!("{:.1}%", p)).unwrap_or_else(|| "-".into());
+                let rss = self.overhead.rss.map(|b| format!("{:.0} MB", b as f64 / 1048576.0)).unwrap_or_else(|| "-".into());
+                ui.label(format!("sia: {} CPU, {}", pct, rss)).on_hover_text("CPU (100% = one core) and resident memory of sia itself");
+                // recording: every sample goes to the file, whatever the window shows
+                let Source::Live(sampler) = &host.source else { return };
+                ui.separator();
+                match &h.recording {
+                    Some((path, rows)) => {
+                        ui.colored_label(Color32::RED, "⏺ REC").on_hover_text(path.display().to_string());
+                        ui.label(format!("{} rows", rows));
+                        if ui.button("Stop").clicked() { sampler.stop_recording(); }
+                    }
+                    None => {
+                        ui.add(egui::TextEdit::singleline(&mut self.record_path).desired_width(260.0)).on_hover_text(".csv for CSV, .jsonl for JSON Lines");
+                        if ui.button("⏺ Record").clicked() {
+                            self.record_status = match sampler.start_recording(Path::new(&self.record_path)) { Ok(()) => String::new(), Err(e) => format!("Not recording: {}", e) };
+                        }
+                    }
+                }
+                if let Some(e) = &h.record_error { ui.colored_label(Color32::RED, format!("Recording stopped: {}", e)); }
+                else if !self.record_status.is_empty() { ui.colored_label(Color32::RED, &self.record_status); }
+                if let Some(e) = &h.push_error { ui.colored_label(Color32::RED, format!("Push failed: {}", e)); }
             });
         });
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.set_min_size(Vec2::new(1200.0, 880.0));
-            let (auto_xmin, auto_xmax) = if self.seconds > self.display_window_secs { (self.seconds - self.display_window_secs, self.seconds) } else { (0.0, self.display_window_secs) };
+            let (auto_xmin, auto_xmax) = if h.now > self.display_window_secs { (h.now - self.display_window_secs, h.now) } else { (0.0, self.display_window_secs) };
 
             // ============ Utilization ============
             ui.heading("Utilization");
-            let util_plot = Plot::new("util").height(220.0).allow_scroll(true).allow_zoom(true).legend(Legend::default().position(Corner::LeftTop));
+            let util_plot = Plot::new("util").x_axis_formatter(x_axis_fmt(self.time_axis, &h)).label_formatter(hover_fmt(self.time_axis, &h)).height(220.0).allow_scroll(true).allow_zoom(true).legend(Legend::default().position(Corner::LeftTop));
             util_plot.show(ui, |plot_ui| {
                 let (xmin, xmax) = (auto_xmin, auto_xmax);
                 plot_ui.set_plot_bounds(PlotBounds::from_min_max([xmin, 0.0], [xmax, 100.0]));
@@ -522,10 +420,12 @@ impl eframe::App for App {
                 let ticks = 4; let step = (ymax - ymin) / (ticks as f64); let mut v = ymin;
                 while v <= ymax + 1e-6 { plot_ui.text(Text::new([xmin, v].into(), format!("{:.0}%", v)).anchor(Align2::LEFT_CENTER)); v += step; }
 
-                plot_ui.line(Line::new(self.cpu_util.points_after(xmin)).name("CPU %").color(theme_color("cpu")));
-                plot_ui.line(Line::new(self.gpu_util.points_after(xmin)).name("GPU %").color(theme_color("gpu")));
-                plot_ui.line(Line::new(self.ram_util.points_after(xmin)).name("RAM %").color(theme_color("ramspd")));
-                plot_ui.line(Line::new(self.vram_util.points_after(xmin)).name("VRAM %").color(theme_color("nvme")));
+                for &i in &host.util {
+                    let ch = &host.channels[i];
+                    plot_series(plot_ui, h.series(i), (xmin, xmax), 1.0, h.max_gap(), &format!("{} %", ch.name), c32(ch.color));
+                }
+
+                suspend_markers(plot_ui, &h, xmin, ymax);
 
                 // right-side labels for symmetry
                 let mut v2 = ymin; while v2 <= ymax + 1e-6 { plot_ui.text(Text::new([xmax, v2].into(), format!("{:.0}%", v2)).anchor(Align2::RIGHT_CENTER)); v2 += step; }
@@ -536,21 +436,31 @@ impl eframe::App for App {
             // ============ Temperatures ============
             ui.heading("Temperatures (°C)");
             let (xmin, xmax) = (auto_xmin, auto_xmax);
-            let temp_plot = Plot::new("temps").height(260.0).allow_scroll(true).allow_zoom(true);
+            let temp_plot = Plot::new("temps").x_axis_formatter(x_axis_fmt(self.time_axis, &h)).label_formatter(hover_fmt(self.time_axis, &h)).height(260.0).allow_scroll(true).allow_zoom(true);
             temp_plot.show(ui, |plot_ui| {
                 // dynamic y
                 let mut mn = f64::INFINITY; let mut mx = f64::NEG_INFINITY;
-                for g in &self.groups { if !g.visible { continue; } for it in &g.items { if !it.visible { continue; } if let Some((a,b)) = self.temp_series[it.idx].min_max_y(xmin, xmax) { if a < mn { mn = a; } if b > mx { mx = b; } } }}
+                for g in &host.groups { if !g.visible { continue; }
+                    for it in &g.items { if !it.visible { continue; }
+                        if let Some((a,b)) = h.series(it.idx).min_max_y(xmin, xmax) { mn = mn.min(a); mx = mx.max(b); }
+                    }
+                    if g.show_thresholds { mx = mx.max(g.hot); }
+                }
                 if !mn.is_finite() || !mx.is_finite() || (mx - mn).abs() < 1e-6 { mn = 0.0; mx = 120.0; }
                 let pad = ((mx - mn) * 0.1).max(2.0); mn = (mn - pad).max(0.0); mx = (mx + pad).min(130.0);
                 plot_ui.set_plot_bounds(PlotBounds::from_min_max([xmin, mn], [xmax, mx]));
 
-                for g in &self.groups { if !g.visible { continue; }
+                for g in &host.groups { if !g.visible { continue; }
                     for it in &g.items { if !it.visible { continue; }
-                        let pts = self.temp_series[it.idx].points_after(xmin);
-                        plot_ui.line(Line::new(pts).name(format!("{}: {}", g.display, it.name)).color(it.color));
+                        plot_series(plot_ui, h.series(it.idx), (xmin, xmax), 1.0, h.max_gap(), &format!("{}: {}", g.display, it.name), c32(it.color));
+                    }
+                    if g.show_thresholds {
+                        let base = g.color;
+                        plot_ui.hline(HLine::new(g.warn).name(format!("{}: warn", g.display)).color(c32(tint(base, 0.4))).style(LineStyle::dashed_loose()));
+                        plot_ui.hline(HLine::new(g.hot).name(format!("{}: hot", g.display)).color(c32(base)).style(LineStyle::dashed_dense()));
                     }
                 }
+                suspend_markers(plot_ui, &h, xmin, mx);
                 let ticks = 4; let step = (mx - mn) / (ticks as f64); let mut v = mn;
                 while v <= mx + 1e-6 { plot_ui.text(Text::new([xmax, v].into(), format!("{:.0}", v)).anchor(Align2::RIGHT_CENTER)); v += step; }
             });
@@ -560,44 +470,30 @@ impl eframe::App for App {
             // ============ Frequencies (GHz) ============
             ui.heading("Frequencies (GHz)");
             let (xmin, xmax) = (auto_xmin, auto_xmax);
-            let freq_plot = Plot::new("freq").height(240.0).allow_scroll(true).allow_zoom(true);
+            let freq_plot = Plot::new("freq").x_axis_formatter(x_axis_fmt(self.time_axis, &h)).label_formatter(hover_fmt(self.time_axis, &h)).height(240.0).allow_scroll(true).allow_zoom(true);
             freq_plot.show(ui, |plot_ui| {
-                // dynamic y across CPU cores + GPU lines
+                // dynamic y across CPU cores + GPU clocks; memory clocks optionally as effective (x2)
+                let div = |id: &str| if self.gpu_mem_effective && id.ends_with("/clock/mem") { 500.0 } else { 1000.0 };
                 let mut mn = f64::INFINITY; let mut mx = f64::NEG_INFINITY;
-                for (i, series) in self.freq_series.iter().enumerate() {
-                    if !self.freq_visible.get(i).copied().unwrap_or(false) { continue; }
-                    if let Some((a,b)) = series.min_max_y(xmin, xmax) { let ag=a/1_000_000.0; let bg=b/1_000_000.0; if ag<mn{mn=ag;} if bg>mx{mx=bg;} }
-                }
-                #[cfg(feature = "nvidia")]
-                {
-                    if self.gpu_freq_graphics_vis { if let Some((a,b)) = self.gpu_clk_graphics.min_max_y(xmin, xmax) { let ag=a/1000.0; let bg=b/1000.0; if ag<mn{mn=ag;} if bg>mx{mx=bg;} } }
-                    if self.gpu_freq_sm_vis       { if let Some((a,b)) = self.gpu_clk_sm.min_max_y(xmin, xmax)       { let ag=a/1000.0; let bg=b/1000.0; if ag<mn{mn=ag;} if bg>mx{mx=bg;} } }
-                    if self.gpu_freq_mem_vis      { if let Some((a,b)) = self.gpu_clk_mem.min_max_y(xmin, xmax)      { let ag=(a/1000.0) * if self.gpu_mem_effective { 2.0 } else { 1.0 }; let bg=(b/1000.0) * if self.gpu_mem_effective { 2.0 } else { 1.0 }; if ag<mn{mn=ag;} if bg>mx{mx=bg;} } }
-                    if self.gpu_freq_video_vis    { if let Some((a,b)) = self.gpu_clk_video.min_max_y(xmin, xmax)    { let ag=a/1000.0; let bg=b/1000.0; if ag<mn{mn=ag;} if bg>mx{mx=bg;} } }
-                }
+                for g in &host.groups { for it in &g.freqs { if !it.visible { continue; }
+                    if let Some((a,b)) = h.series(it.idx).min_max_y(xmin, xmax) { let d = div(&it.id); mn=mn.min(a/d); mx=mx.max(b/d); }
+                }}
                 if !mn.is_finite() || !mx.is_finite() || (mx - mn).abs() < 1e-6 { mn = 0.1; mx = 10.0; }
                 let pad = ((mx - mn) * 0.08).max(0.05); mn = (mn - pad).max(0.0); mx = (mx + pad).min(12.0);
                 plot_ui.set_plot_bounds(PlotBounds::from_min_max([xmin, mn], [xmax, mx]));
 
-                for (i, series) in self.freq_series.iter().enumerate() {
-                    if !self.freq_visible.get(i).copied().unwrap_or(false) { continue; }
-                    let name = format!("CPU Core {}", FREQ_SENSORS.get(i).map(|s| s.core).unwrap_or(i));
-                    let pts = series.points_after_scaled(xmin, 1_000_000.0);
-                    plot_ui.line(Line::new(pts).name(name).color(self.freq_colors[i % self.freq_colors.len()]));
-                }
-                #[cfg(feature = "nvidia")]
-                {
-                    if self.gpu_freq_graphics_vis { let pts = self.gpu_clk_graphics.points_after_scaled(xmin, 1000.0); plot_ui.line(Line::new(pts).name("GPU Graphics")); }
-                    if self.gpu_freq_sm_vis       { let pts = self.gpu_clk_sm.points_after_scaled(xmin, 1000.0);       plot_ui.line(Line::new(pts).name("GPU SM")); }
-                    if self.gpu_freq_mem_vis      { let div = 1000.0 / if self.gpu_mem_effective { 2.0 } else { 1.0 }; let pts = self.gpu_clk_mem.points_after_scaled(xmin, div); let label = if self.gpu_mem_effective { "GPU Memory (effective)" } else { "GPU Memory" }; plot_ui.line(Line::new(pts).name(label)); }
-                    if self.gpu_freq_video_vis    { let pts = self.gpu_clk_video.points_after_scaled(xmin, 1000.0);    plot_ui.line(Line::new(pts).name("GPU Video")); }
-                }
+                for g in &host.groups { for it in &g.freqs { if !it.visible { continue; }
+                    let d = div(&it.id);
+                    let name = if d < 1000.0 { format!("{} (effective)", it.name) } else { it.name.clone() };
+                    plot_series(plot_ui, h.series(it.idx), (xmin, xmax), d, h.max_gap(), &name, c32(it.color));
+                }}
+                suspend_markers(plot_ui, &h, xmin, mx);
                 let ticks = 4; let step = (mx - mn) / (ticks as f64); let mut v = mn;
                 while v <= mx + 1e-6 { plot_ui.text(Text::new([xmax, v].into(), format!("{:.2} GHz", v)).anchor(Align2::RIGHT_CENTER)); v += step; }
             });
 
             // ============ Legends outside ============
-            match self.legend_place { LegendPlacement::Footer => self.footer_legend(ui), LegendPlacement::Side => self.side_legend(ui) }
+            match self.legend_place { LegendPlacement::Footer => host.footer_legend(ui, &h), LegendPlacement::Side => host.side_legend(ui, &h) }
 
             ui.separator();
 
@@ -606,13 +502,19 @@ impl eframe::App for App {
                 ui.heading("Display");
                 ui.horizontal(|ui| {
                     ui.label("Window (seconds) before scroll):");
-                    ui.add(egui::Slider::new(&mut self.display_window_secs, 30.0..=900.0));
+                    ui.add(egui::Slider::new(&mut self.display_window_secs, 10.0..=self.max_window_secs).logarithmic(true));
                     egui::ComboBox::from_label("Legend placement")
                         .selected_text(match self.legend_place { LegendPlacement::Footer => "Footer", LegendPlacement::Side => "Side" })
                         .show_ui(ui, |ui| {
                             ui.selectable_value(&mut self.legend_place, LegendPlacement::Footer, "Footer");
                             ui.selectable_value(&mut self.legend_place, LegendPlacement::Side, "Side strip");
                         });
+                    egui::ComboBox::from_label("Time axis")
+                        .selected_text(match self.time_axis { TimeAxis::Elapsed => "Seconds", TimeAxis::Clock => "Clock time" })
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.time_axis, TimeAxis::Elapsed, "Seconds");
+                            ui.selectable_value(&mut self.time_axis, TimeAxis::Clock, "Clock time");
+                        });
                     ui.separator();
                     ui.label("Font size");
                     let resp = ui.add(egui::Slider::new(&mut self.pending_ui_font_size, 10.0..=22.0));
@@ -630,9 +532,9 @@ impl eframe::App for App {
                 ui.heading("Sensors");
                 let cols = 2;
                 egui::Grid::new("sensor_grid").num_columns(cols).striped(true).min_col_width(500.0).spacing([18.0, 8.0]).show(ui, |ui| {
-                    for g in &mut self.groups {
-                        if g.display.starts_with("CPU") {
-                            egui::CollapsingHeader::new("CPU").id_source("grp_cpu").default_open(false).show(ui, |ui| {
+                    for g in &mut host.groups {
+                        if g.kind == "cpu" {
+                            egui::CollapsingHeader::new(g.display.clone()).id_source(format!("grp_{}", g.key)).default_open(false).show(ui, |ui| {
 
                                 ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
                                     let avail    = ui.available_width();
@@ -646,25 +548,22 @@ impl eframe::App for App {
                                     let layout = egui::Layout::top_down(egui::Align::LEFT);
                                     ui.allocate_ui_with_layout(egui::vec2(left_px, 0.0), layout, |ui| {
                                         ui.label(RichText::new("Core temperatures").strong());
-                                        for it in &mut g.items { ui.checkbox(&mut it.visible, &it.name); }
+                                        ui.checkbox(&mut g.show_thresholds, "Show warn/hot thresholds");
+                                        for it in &mut g.items { ui.checkbox(&mut it.visible, &it.name).on_hover_text(&it.id); }
                                     });
                                     ui.allocate_ui_with_layout(egui::vec2(right_px, 0.0), layout, |ui| {
                                         ui.label(RichText::new("Core frequencies").strong());
                                         ui.horizontal(|ui| {
-                                            if ui.button("All").clicked()  { for v in &mut self.freq_visible { *v = true; } }
-                                            if ui.button("None").clicked() { for v in &mut self.freq_visible { *v = false; } }
+                                            if ui.button("All").clicked()  { for it in &mut g.freqs { it.visible = true; } }
+                                            if ui.button("None").clicked() { for it in &mut g.freqs { it.visible = false; } }
                                         });
-                                        for (i, fs) in FREQ_SENSORS.iter().enumerate() {
-                                            let mut vis = self.freq_visible[i];
-                                            let label = format!("CPU Core {}", fs.core);
-                                            ui.checkbox(&mut vis, label);
-                                            self.freq_visible[i] = vis;
-                                        }
+                                        for it in &mut g.freqs { ui.checkbox(&mut it.visible, &it.name).on_hover_text(&it.id); }
                                     });
                                 });
                             });
-                        } else if g.display.starts_with("GPU") {
-                            egui::CollapsingHeader::new("GPU").id_source("grp_gpu").default_open(false).show(ui, |ui| {
+                        } else if g.kind == "gpu" {
+                            let has_mem_clock = g.freqs.iter().any(|it| it.id.ends_with("/clock/mem"));
+                            egui::CollapsingHeader::new(g.display.clone()).id_source(format!("grp_{}", g.key)).default_open(false).show(ui, |ui| {
                                ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
 
                                     let avail    = ui.available_width();
@@ -678,69 +577,380 @@ impl eframe::App for App {
                                     let layout = egui::Layout::top_down(egui::Align::LEFT);
                                     ui.allocate_ui_with_layout(egui::vec2(left_px, 0.0), layout, |ui| {
                                         ui.label(RichText::new("Temperatures").strong());
-                                        for it in &mut g.items { ui.checkbox(&mut it.visible, &it.name); }
+                                        ui.checkbox(&mut g.show_thresholds, "Show warn/hot thresholds");
+                                        for it in &mut g.items { ui.checkbox(&mut it.visible, &it.name).on_hover_text(&it.id); }
                                     });
                                     ui.allocate_ui_with_layout(egui::vec2(right_px, 0.0), layout, |ui| {
                                         ui.label(RichText::new("Frequencies").strong());
-                                        #[cfg(feature = "nvidia")]
-                                        {
-                                            ui.checkbox(&mut self.gpu_freq_graphics_vis, "GPU Graphics");
-                                            ui.checkbox(&mut self.gpu_freq_sm_vis,       "GPU SM");
-                                            ui.checkbox(&mut self.gpu_mem_effective,     "Show memory as effective (x2)");
-                                            ui.checkbox(&mut self.gpu_freq_mem_vis,      "GPU Memory");
-                                            ui.checkbox(&mut self.gpu_freq_video_vis,    "GPU Video");
-                                        }
+                                        if has_mem_clock { ui.checkbox(&mut self.gpu_mem_effective, "Show memory as effective (x2)"); }
+                                        for it in &mut g.freqs { ui.checkbox(&mut it.visible, &it.name).on_hover_text(&it.id); }
                                     });
                                 });
                             });
                         } else {
-                            egui::CollapsingHeader::new(g.display.clone()).id_source(format!("grp_other_{}", g.display)).default_open(false).show(ui, |ui| { for it in &mut g.items { ui.checkbox(&mut it.visible, &it.name); } });
+                            egui::CollapsingHeader::new(g.display.clone()).id_source(format!("grp_{}", g.key)).default_open(false).show(ui, |ui| {
+                                ui.checkbox(&mut g.show_thresholds, "Show warn/hot thresholds");
+                                for it in &mut g.items { ui.checkbox(&mut it.visible, &it.name).on_hover_text(&it.id); }
+                            });
                         }
                         if cols > 1 { ui.end_row(); }
                     }
                 });
+                ui.separator();
+
+                // a recording already holds corrected values
+                let Source::Live(sampler) = &host.source else { return };
+                egui::CollapsingHeader::new("Calibration").id_source("calibration").default_open(false).show(ui, |ui| {
+                    ui.label("Corrected = raw × scale + offset. Saved per sensor id; applies to new samples.");
+                    egui::Grid::new("calib_grid").num_columns(5).striped(true).spacing([18.0, 6.0]).show(ui, |ui| {
+                        for h in ["Sensor", "Raw (°C)", "Corrected (°C)", "Offset (°C)", "Scale"] { ui.label(RichText::new(h).strong()); }
+                        ui.end_row();
+                        for g in &host.groups { for it in &g.items {
+                            ui.label(format!("{}: {}", g.display, it.name)).on_hover_text(&it.id);
+                            let fmt = |v: Option<f64>| v.map(|v| format!("{:.1}", v)).unwrap_or_else(|| "–".into());
+                            ui.label(fmt(h.raw[it.idx]));
+                            ui.label(fmt(h.series(it.idx).last_y()));
+                            let mut c = self.calib.get(&it.id);
+                            let changed = ui.add(egui::DragValue::new(&mut c.offset).speed(0.1).fixed_decimals(1)).changed()
+                                | ui.add(egui::DragValue::new(&mut c.scale).speed(0.001).fixed_decimals(3)).changed();
+                            if changed { self.calib.set(&it.id, c); sampler.set_calibration(&it.id, c); }
+                            ui.end_row();
+                        }}
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button("Save calibration").clicked() {
+                            self.calib_status = match self.calib.save() { Ok(()) => "Saved".into(), Err(e) => format!("Not saved: {}", e) };
+                        }
+                        ui.label(&self.calib_status);
+                    });
+                });
             });
         });
     }
 }
 
-impl App {
-    fn footer_legend(&self, ui: &mut egui::Ui) {
-        ui.horizontal_wrapped(|ui| {
-            ui.label(RichText::new("Legend:").strong());
-            for g in &self.groups { if !g.visible { continue; } for it in &g.items { if !it.visible { continue; }
+impl Host {
+    /// Visible temperatures with their warn/hot state, plus any other visible series that has missed reads,
+    /// so a flaky sensor shows up even where the line break is easy to overlook.
+    fn legend_entries(&self, h: &History) -> Vec<(Color32, String)> {
+        let missed = |idx: usize, text: &mut String| { let n = h.series(idx).missed(); if n > 0 { text.push_str(&format!(" ({} missed)", n)); } };
+        let mut out = vec![];
+        for &i in &self.util { if h.series(i).missed() == 0 { continue; }
+            let mut text = self.channels[i].name.clone(); missed(i, &mut text);
+            out.push((c32(self.channels[i].color), text));
+        }
+        for g in &self.groups { if !g.visible { continue; }
+            for it in &g.items { if !it.visible { continue; }
                 let mut text = it.name.clone();
-                let hot = self.temp_series[it.idx].last_y().map(|y| y >= g.hot).unwrap_or(false);
-                let warn = self.temp_series[it.idx].last_y().map(|y| y >= g.warn).unwrap_or(false);
+                let hot = h.series(it.idx).last_y().map(|y| y >= g.hot).unwrap_or(false);
+                let warn = h.series(it.idx).last_y().map(|y| y >= g.warn).unwrap_or(false);
                 if hot { text.push_str(" 🔥"); } else if warn { text.push_str(" 🥵"); }
-                ui.horizontal(|ui| { ui.colored_label(it.color, "●"); ui.label(text); });
-            }}
+                missed(it.idx, &mut text);
+                out.push((c32(it.color), text));
+            }
+            for it in &g.freqs { if !it.visible || h.series(it.idx).missed() == 0 { continue; }
+                let mut text = it.name.clone(); missed(it.idx, &mut text);
+                out.push((c32(it.color), text));
+            }
+        }
+        out
+    }
+    fn footer_legend(&self, ui: &mut egui::Ui, h: &History) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label(RichText::new("Legend:").strong());
+            for (color, text) in self.legend_entries(h) { ui.horizontal(|ui| { ui.colored_label(color, "●"); ui.label(text); }); }
         });
     }
-    fn side_legend(&self, ui: &mut egui::Ui) {
+    fn side_legend(&self, ui: &mut egui::Ui, h: &History) {
         ui.horizontal(|ui| {
             ui.vertical(|ui| {
                 ui.label(RichText::new("Legend").strong());
-                for g in &self.groups { if !g.visible { continue; } for it in &g.items { if !it.visible { continue; }
-                    let mut text = it.name.clone();
-                    let hot = self.temp_series[it.idx].last_y().map(|y| y >= g.hot).unwrap_or(false);
-                    let warn = self.temp_series[it.idx].last_y().map(|y| y >= g.warn).unwrap_or(false);
-                    if hot { text.push_str(" 🔥"); } else if warn { text.push_str(" 🥵"); }
-                    ui.horizontal(|ui| { ui.colored_label(it.color, "●"); ui.label(text); });
-                }}
+                for (color, text) in self.legend_entries(h) { ui.horizontal(|ui| { ui.colored_label(color, "●"); ui.label(text); }); }
+            });
+        });
+    }
+}
+
+// ===================== Dashboard =====================
+/// A host's state: its hottest sensor against the group's thresholds, or whether its agent is reachable.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Alert { Ok, Warn, Hot, Offline }
+
+impl Alert {
+    fn of(g: &SensorGroup, v: f64) -> Self { if v >= g.hot { Alert::Hot } else if v >= g.warn { Alert::Warn } else { Alert::Ok } }
+    fn color(self) -> Color32 {
+        match self { Alert::Ok => Color32::from_rgb(76, 175, 80), Alert::Warn => Color32::from_rgb(255, 152, 0), Alert::Hot => Color32::RED, Alert::Offline => Color32::GRAY }
+    }
+    fn label(self) -> &'static str { match self { Alert::Ok => "OK", Alert::Warn => "WARN", Alert::Hot => "HOT", Alert::Offline => "OFFLINE" } }
+}
+
+fn unix(t: SystemTime) -> f64 { t.duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs_f64()).unwrap_or(0.0) }
+
+impl Host {
+    /// Hottest current temperature of each group that has one.
+    fn hottest<'a>(&'a self, h: &History) -> Vec<(&'a SensorGroup, &'a str, f64)> {
+        self.groups.iter().filter_map(|g| {
+            g.items.iter().filter_map(|it| h.series(it.idx).last_y().map(|v| (it.name.as_str(), v))).max_by(|a, b| a.1.total_cmp(&b.1)).map(|(n, v)| (g, n, v))
+        }).collect()
+    }
+
+    fn alert(&self, hottest: &[(&SensorGroup, &str, f64)]) -> Alert {
+        if matches!(&self.source, Source::Remote(r) if r.status().is_some()) { return Alert::Offline; }
+        hottest.iter().map(|(g, _, v)| Alert::of(g, *v)).max().unwrap_or(Alert::Ok)
+    }
+}
+
+impl App {
+    /// Every host as a row (alert state, CPU/GPU utilization sparkline, hottest sensor per group), and one
+    /// sensor compared across hosts on a wall-clock axis. Clicking a host opens its full view.
+    fn dashboard(&mut self, ctx: &egui::Context) {
+        let mut open = None;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::vertical().auto_shrink([false; 2]).show(ui, |ui| {
+                ui.heading("Fleet");
+                egui::Grid::new("fleet").num_columns(4).striped(true).spacing([18.0, 8.0]).show(ui, |ui| {
+                    for h in ["Host", "State", "CPU / GPU (2 min)", "Hottest per group"] { ui.label(RichText::new(h).strong()); }
+                    ui.end_row();
+                    for (i, host) in self.hosts.iter().enumerate() {
+                        let history = host.source.history();
+                        let h = history.lock().unwrap_or_else(|e| e.into_inner());
+                        if ui.button(RichText::new(&host.name).strong()).on_hover_text("Open the full view").clicked() { open = Some(i); }
+                        let hottest = host.hottest(&h);
+                        let alert = host.alert(&hottest);
+                        let badge = ui.label(RichText::new(alert.label()).color(alert.color()).strong());
+                        if let Source::Remote(r) = &host.source { if let Some(e) = r.status() { badge.on_hover_text(e); } }
+                        let (xmin, xmax) = (h.now - 120.0, h.now.max(120.0));
+                        Plot::new(("spark", i)).width(240.0).height(44.0).show_axes(false).show_grid(false).show_x(false).show_y(false)
+                            .allow_drag(false).allow_zoom(false).allow_scroll(false).allow_boxed_zoom(false).show(ui, |plot_ui| {
+                                plot_ui.set_plot_bounds(PlotBounds::from_min_max([xmin, 0.0], [xmax, 100.0]));
+                                for &c in host.util.iter().filter(|&&c| host.channels[c].id.ends_with("/util") && host.channels[c].id != "ram/util") {
+                                    plot_series(plot_ui, h.series(c), (xmin, xmax), 1.0, h.max_gap(), &host.channels[c].name, c32(host.channels[c].color));
+                                }
+                            });
+                        ui.horizontal_wrapped(|ui| {
+                            for (g, name, v) in &hottest {
+                                ui.label(RichText::new(format!("{} {:.0}°", g.display, v)).color(Alert::of(g, *v).color()))
+                                    .on_hover_text(format!("{}: {} {:.1} °C (warn {:.0}, hot {:.0})", g.display, name, v, g.warn, g.hot));
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+                self.compare_plot(ui);
             });
         });
+        if let Some(i) = open { self.host = i; self.dashboard = false; }
+    }
+
+    /// The same sensor (group kind and sensor name, e.g. CPU / Package id 0) on every host that has it.
+    fn compare_plot(&mut self, ui: &mut egui::Ui) {
+        let mut keys: Vec<((String, String), String)> = vec![];
+        for g in self.hosts.iter().flat_map(|h| &h.groups) { for it in &g.items {
+            let key = (g.kind.clone(), it.name.clone());
+            if !keys.iter().any(|(k, _)| *k == key) { keys.push((key, format!("{}: {}", g.display, it.name))); }
+        }}
+        keys.sort_by(|a, b| natural_cmp(&a.1, &b.1));
+        if self.compare.is_none() { self.compare = keys.iter().find(|(k, _)| k.0 == "cpu").or(keys.first()).map(|(k, _)| k.clone()); }
+        ui.horizontal(|ui| {
+            ui.heading("Compare across hosts");
+            let selected = keys.iter().find(|(k, _)| Some(k) == self.compare.as_ref()).map(|(_, l)| l.clone()).unwrap_or_default();
+            egui::ComboBox::from_id_source("compare_sensor").selected_text(selected).width(320.0).show_ui(ui, |ui| {
+                for (k, label) in &keys { ui.selectable_value(&mut self.compare, Some(k.clone()), label); }
+            });
+        });
+        let Some((kind, name)) = self.compare.clone() else { return };
+
+        // every host on one x axis: seconds since this window opened, by wall clock
+        let (t0, axis) = (self.start_wall, self.time_axis);
+        let xmax = unix(SystemTime::now()) - unix(t0);
+        let xmin = xmax - self.display_window_secs;
+        let fmt_x = move |x: f64| match axis { TimeAxis::Elapsed => format!("{:.0}", x), TimeAxis::Clock => clock(t0 + Duration::from_secs_f64(x.max(0.0))) };
+        let pal = sia_core::taxonomy::palette();
+        Plot::new("compare").height(320.0).legend(Legend::default().position(Corner::LeftTop))
+            .x_axis_formatter(move |m, _| fmt_x(m.value)).label_formatter(move |n, p| format!("{}\n{}\n{:.1} °C", n, fmt_x(p.x), p.y))
+            .show(ui, |plot_ui| {
+                let (mut mn, mut mx) = (f64::INFINITY, f64::NEG_INFINITY);
+                for (hi, host) in self.hosts.iter().enumerate() {
+                    let history = host.source.history();
+                    let h = history.lock().unwrap_or_else(|e| e.into_inner());
+                    let off = unix(h.wall_at_zero) - unix(t0);
+                    let matches: Vec<_> = host.groups.iter().filter(|g| g.kind == kind).flat_map(|g| g.items.iter().filter(|it| it.name == name).map(move |it| (g, it))).collect();
+                    for (g, it) in &matches {
+                        let label = if matches.len() > 1 { format!("{} ({})", host.name, g.display) } else { host.name.clone() };
+                        let color = c32(pal[hi % pal.len()]);
+                        let lod = Lod { x_max: xmax - off, px: plot_ui.response().rect.width() as usize };
+                        for seg in h.series(it.idx).segments_lod(xmin - off, 1.0, h.max_gap(), lod).iter() {
+                            let pts: Vec<[f64; 2]> = seg.iter().map(|[x, y]| [x + off, *y]).collect();
+                            for p in &pts { mn = mn.min(p[1]); mx = mx.max(p[1]); }
+                            plot_ui.line(Line::new(PlotPoints::from(pts)).name(&label).color(color));
+                        }
+                    }
+                }
+                if !mn.is_finite() || !mx.is_finite() || (mx - mn).abs() < 1e-6 { mn = 0.0; mx = 120.0; }
+                let pad = ((mx - mn) * 0.1).max(2.0);
+                plot_ui.set_plot_bounds(PlotBounds::from_min_max([xmin, (mn - pad).max(0.0)], [xmax, mx + pad]));
+            });
     }
 }
 
 // ===================== Entry =====================
+/// Where live samples go besides the screen: `--record`, `--listen`, `--push` and `--mqtt`.
+#[derive(Default)]
+pub struct Outputs { pub record: Option<PathBuf>, pub listen: Option<SocketAddr>, pub push: Option<PushConfig>, pub mqtt: Option<MqttConfig> }
+
+impl Outputs {
+    /// Starts the network outputs on `sampler`; recording is left to the caller, which reports failure its own way.
+    fn start(&self, sampler: &Sampler) -> std::io::Result<()> {
+        if let Some(a) = self.listen { sampler.listen(a)?; }
+        if let Some(p) = &self.push { sampler.push(p.clone())?; }
+        if let Some(m) = &self.mqtt { sampler.publish_mqtt(m.clone())?; }
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+#[command(name = "sia", version, about = "SIA - System Information Analyzer", args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Cmd>,
+    /// Print readings in the terminal instead of opening a window
+    #[arg(long, conflicts_with = "tui")]
+    headless: bool,
+    /// Full-screen terminal UI with the same plots as the window, e.g. over SSH
+    #[arg(long)]
+    tui: bool,
+    /// With --headless: print one line with every reading and exit
+    #[arg(long, requires = "headless", conflicts_with = "interval")]
+    once: bool,
+    /// With --headless: print one line per sample every SECS seconds instead of a table
+    #[arg(long, value_name = "SECS", requires = "headless")]
+    interval: Option<f64>,
+    /// Record every sample to FILE: CSV, or JSON Lines if it ends in .jsonl
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+    /// Play back a recording made with --record instead of reading this machine's sensors
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["headless", "tui", "record"])]
+    replay: Option<PathBuf>,
+    /// Serve Prometheus metrics at http://ADDR/metrics, e.g. 127.0.0.1:9464
+    #[arg(long, value_name = "ADDR", conflicts_with_all = ["replay", "once"])]
+    listen: Option<SocketAddr>,
+    /// Stream samples to TARGET: udp://HOST:PORT, tcp://HOST:PORT, unix:///PATH or - for stdout
+    #[arg(long, value_name = "TARGET", conflicts_with = "replay")]
+    push: Option<Target>,
+    /// With --push: influx (line protocol) or graphite (plaintext)
+    #[arg(long, value_name = "FORMAT", default_value = "influx", requires = "push")]
+    push_format: LineFormat,
+    /// With --push: send a batch every SECS seconds
+    #[arg(long, value_name = "SECS", default_value_t = 10.0, requires = "push")]
+    push_every: f64,
+    /// Publish to an MQTT broker: mqtt://[USER[:PASS]@]HOST[:PORT][/PREFIX] (prefix defaults to sia)
+    #[arg(long, value_name = "URL", conflicts_with = "replay")]
+    mqtt: Option<MqttConfig>,
+    /// With --mqtt: one JSON message per sample on <prefix>/<host>/snapshot instead of one per sensor
+    #[arg(long, requires = "mqtt")]
+    mqtt_snapshot: bool,
+    /// With --mqtt: publish Home Assistant discovery configs under PREFIX
+    #[arg(long, value_name = "PREFIX", num_args = 0..=1, default_missing_value = "homeassistant", requires = "mqtt")]
+    mqtt_discovery: Option<String>,
+    /// Show an agent's sensors instead of this machine's: HOST[:PORT], tcp://HOST[:PORT] or ssh://[USER@]HOST.
+    /// Repeat to watch several machines
+    #[arg(long, value_name = "AGENT", conflicts_with_all = ["headless", "tui", "replay", "record", "listen", "push", "mqtt"])]
+    connect: Vec<Endpoint>,
+    /// Samples per second, up to 20 [default: 1]
+    #[arg(long, value_name = "HZ")]
+    rate: Option<f64>,
+    /// Seconds of raw samples to keep; older ones are kept as 10 s and 1 min averages for a day [default: 900]
+    #[arg(long, value_name = "SECS")]
+    history: Option<f64>,
+    /// Seconds shown in the plots at start [default: as last time]
+    #[arg(long, value_name = "SECS")]
+    window: Option<f64>,
+    /// Window size and position: WxH or WxH+X+Y [default: 1230x1130]
+    #[arg(long, value_name = "GEOMETRY", value_parser = |s: &str| s.parse::<config::Geometry>().map(|_| s.to_string()))]
+    geometry: Option<String>,
+    /// Read startup options from FILE instead of ~/.config/sia/config.toml
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Apply a named set of options from the config file, or a built-in one: bench (10 Hz), soak (0.1 Hz)
+    #[arg(long, value_name = "NAME")]
+    preset: Option<String>,
+    /// Skip a sensor backend; repeat or separate with commas
+    #[arg(long, value_name = "COLLECTOR", value_delimiter = ',', value_parser = clap::builder::PossibleValuesParser::new(CollectorConfig::BACKENDS))]
+    disable: Vec<String>,
+    /// Use a sensor backend the config file disables
+    #[arg(long, value_name = "COLLECTOR", value_delimiter = ',', value_parser = clap::builder::PossibleValuesParser::new(CollectorConfig::BACKENDS))]
+    enable: Vec<String>,
+}
+
+#[derive(clap::Subcommand)]
+enum Cmd {
+    /// Sample this machine and stream to viewers (`sia --connect`), e.g. on a machine without a display
+    Agent {
+        /// Address to accept viewers on; use 0.0.0.0:7979 to accept them from other machines
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:7979", conflicts_with = "stdio")]
+        bind: SocketAddr,
+        /// Stream to stdout instead, for `sia --connect ssh://HOST`
+        #[arg(long)]
+        stdio: bool,
+        /// Seconds between samples [default: from the config file's rate, else 1]
+        #[arg(long, value_name = "SECS")]
+        interval: Option<f64>,
+    },
+}
+
 fn main() -> eframe::Result<()> {
-    let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([1230.0, 1130.0])
-            .with_min_inner_size([950.0, 700.0])
-            .with_title("SIA - System Information Analyzer - © David Crawley 2025"),
-        ..Default::default()
+    let cli = Cli::parse();
+    let layer = Layer {
+        rate: cli.rate, history: cli.history, window: cli.window, geometry: cli.geometry.clone(),
+        collectors: cli.disable.iter().map(|n| (n.clone(), false)).chain(cli.enable.iter().map(|n| (n.clone(), true))).collect(),
+    };
+    let cfg = match Config::resolve(cli.config.as_deref(), cli.preset.as_deref(), layer) {
+        Ok(c) => c,
+        Err(e) => { eprintln!("sia: {}", e); std::process::exit(1); }
+    };
+    if let Some(Cmd::Agent { bind, stdio, interval }) = cli.command {
+        match agent::run(&agent::Options { bind, stdio, interval: interval.unwrap_or(1.0 / cfg.rate), collectors: cfg.collectors }) {
+            Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => { eprintln!("sia: {}", e); std::process::exit(1); }
+            _ => return Ok(()),
+        }
+    }
+    let outputs = Outputs {
+        record: cli.record.clone(),
+        listen: cli.listen,
+        push: cli.push.clone().map(|target| PushConfig { target, format: cli.push_format, every: Duration::from_secs_f64(cli.push_every.max(0.0)) }),
+        mqtt: cli.mqtt.clone().map(|m| MqttConfig { snapshot: cli.mqtt_snapshot, discovery: cli.mqtt_discovery.clone(), period: cfg.period(), ..m }),
     };
-    eframe::run_native("SIA - System Information Analyzer", options, Box::new(|_cc| Ok(Box::new(App::new(5 * 60, 1.0)))) )
+    if cli.tui {
+        if let Err(e) = tui::run(&cfg, &outputs) { eprintln!("sia: {}", e); std::process::exit(1); }
+        return Ok(());
+    }
+    if cli.headless {
+        match headless::run(&headless::Options { once: cli.once, interval: cli.interval, cfg: cfg.clone(), outputs }) {
+            Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => { eprintln!("sia: {}", e); std::process::exit(1); }
+            _ => return Ok(()),
+        }
+    }
+    let rules = || Rules::load(cfg.collectors.rules_file.as_deref());
+    let replay = match cli.replay.as_deref().map(|p| Recording::load(p, &rules())).transpose() {
+        Ok(r) => r,
+        Err(e) => { eprintln!("sia: {}", e); std::process::exit(1); }
+    };
+    // an unreachable agent is shown offline and retried, rather than keeping the others from being viewed
+    let remotes: Vec<Remote> = cli.connect.iter().map(|ep| Remote::spawn(ep.clone(), cfg.capacity(), rules())).collect();
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_inner_size(cfg.geometry.size)
+        .with_min_inner_size([950.0, 700.0])
+        .with_title("SIA - System Information Analyzer - © David Crawley 2025");
+    if let Some(pos) = cfg.geometry.pos { viewport = viewport.with_position(pos); }
+    let options = eframe::NativeOptions { viewport, ..Default::default() };
+    eframe::run_native("SIA - System Information Analyzer", options, Box::new(move |cc| {
+        let app = match replay {
+            Some(rec) => App::replay(rec, &cfg),
+            None if !remotes.is_empty() => App::remote(remotes, &cfg),
+            None => App::new(&cfg, outputs)?,
+        };
+        app.repaint_on_samples(&cc.egui_ctx);
+        Ok(Box::new(app))
+    }))
 }
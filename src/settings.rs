@@ -0,0 +1,71 @@
+//! View settings kept between runs in `~/.config/sia/settings.toml`: typography, plot window, legend and time
+//! axis, and which sensors are shown, keyed by stable sensor id so they survive reordering and new hardware:
+//!
+//! ```toml
+//! font_size = 14.0
+//! window_secs = 120.0
+//! legend = "footer"
+//!
+//! [sensors]
+//! "coretemp@platform/coretemp.0/temp1" = true
+//! "cpu3/freq" = false
+//! ```
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LegendPlacement { Footer, Side }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeAxis { Elapsed, Clock }
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub font_size: f32,
+    /// sRGBA, unmultiplied.
+    pub font_color: [u8; 4],
+    pub window_secs: f64,
+    pub legend: LegendPlacement,
+    pub time_axis: TimeAxis,
+    /// GPU memory clock shown as the effective (doubled) rate.
+    pub gpu_mem_effective: bool,
+    /// Visibility by sensor id, temperatures and clocks alike. Sensors not seen this run are kept.
+    pub sensors: BTreeMap<String, bool>,
+    /// Warn/hot threshold lines by group key.
+    pub thresholds: BTreeMap<String, bool>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self { font_size: 14.0, font_color: [255; 4], window_secs: 120.0, legend: LegendPlacement::Footer, time_axis: TimeAxis::Elapsed, gpu_mem_effective: false, sensors: BTreeMap::new(), thresholds: BTreeMap::new(), path: None }
+    }
+}
+
+impl Settings {
+    /// `settings.toml` in the config directory; a missing or unreadable file gives the defaults.
+    pub fn load() -> Self {
+        let path = sia_core::config_dir().map(|d| d.join("settings.toml"));
+        let mut s: Self = path.as_ref().filter(|p| p.exists()).and_then(|p| {
+            let parsed = fs::read_to_string(p).map_err(|e| e.to_string()).and_then(|s| toml::from_str(&s).map_err(|e| e.to_string()));
+            parsed.map_err(|e| eprintln!("sia: ignoring {}: {}", p.display(), e)).ok()
+        }).unwrap_or_default();
+        s.path = path;
+        s
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = self.path.as_ref().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory (HOME unset)"))?;
+        let with_path = |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", path.display(), e));
+        if let Some(dir) = path.parent() { fs::create_dir_all(dir).map_err(with_path)?; }
+        let text = toml::to_string(self).map_err(|e| with_path(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+        fs::write(path, text).map_err(with_path)
+    }
+}
@@ -0,0 +1,182 @@
+//! `sia --tui`: the GUI's three plots as terminal charts, for use over SSH. Same sampler, groups, colors and
+//! visibility defaults as the egui app.
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols::Marker;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Axis, Block, Chart, Dataset, GraphType, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+use crate::config::Config;
+use crate::Outputs;
+use sia_core::{Channel, Collector, History, Kind, Lod, Rgb, Sampler, SensorGroup};
+use std::io;
+use std::time::Duration;
+
+fn color(c: Rgb) -> Color { Color::Rgb(c.0, c.1, c.2) }
+
+/// One series as its runs of points (split at gaps), in its color.
+type Trace = (Color, Vec<Vec<(f64, f64)>>);
+
+/// A line in the sensor list: a group header, one of its temperatures or one of its clocks.
+#[derive(Clone, Copy)]
+enum Row { Group(usize), Temp(usize, usize), Freq(usize, usize) }
+
+struct Tui {
+    sampler: Sampler,
+    channels: Vec<Channel>,
+    groups: Vec<SensorGroup>,
+    util: Vec<usize>,
+    rows: Vec<Row>,
+    list: ListState,
+    window_secs: f64,
+    max_window_secs: f64,
+}
+
+pub fn run(cfg: &Config, outputs: &Outputs) -> io::Result<()> {
+    let collector = Collector::new(&cfg.collectors);
+    let channels = collector.channels().to_vec();
+    let groups = collector.groups().to_vec();
+    let util = channels.iter().enumerate().filter(|(_, c)| c.kind == Kind::Utilization).map(|(i, _)| i).collect();
+    let mut rows = vec![];
+    for (gi, g) in groups.iter().enumerate() {
+        rows.push(Row::Group(gi));
+        rows.extend((0..g.items.len()).map(|i| Row::Temp(gi, i)));
+        rows.extend((0..g.freqs.len()).map(|i| Row::Freq(gi, i)));
+    }
+    let sampler = Sampler::spawn(collector, cfg.period(), cfg.capacity());
+    if let Some(p) = &outputs.record { sampler.start_recording(p)?; }
+    outputs.start(&sampler)?;
+    let mut app = Tui { sampler, channels, groups, util, rows, list: ListState::default().with_selected(Some(0)), window_secs: cfg.window.unwrap_or(120.0), max_window_secs: cfg.history.max(sia_core::series::TIERED_SPAN) };
+
+    // ratatui::init restores the terminal on panic as well
+    let mut terminal = ratatui::init();
+    let res = app.event_loop(&mut terminal);
+    ratatui::restore();
+    res
+}
+
+impl Tui {
+    fn event_loop(&mut self, terminal: &mut ratatui::DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|f| self.draw(f))?;
+            if !event::poll(Duration::from_millis(250))? { continue; }
+            let Event::Key(key) = event::read()? else { continue };
+            if key.kind != KeyEventKind::Press { continue; }
+            let sel = self.list.selected().unwrap_or(0);
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Up | KeyCode::Char('k') => self.list.select(Some(sel.saturating_sub(1))),
+                KeyCode::Down | KeyCode::Char('j') => self.list.select(Some((sel + 1).min(self.rows.len().saturating_sub(1)))),
+                KeyCode::Char(' ') | KeyCode::Enter => self.toggle(sel),
+                KeyCode::Char('t') => if let Some(g) = self.rows.get(sel).map(|r| self.group_of(*r)) { self.groups[g].show_thresholds = !self.groups[g].show_thresholds; },
+                KeyCode::Char('+') | KeyCode::Char('=') => self.window_secs = (self.window_secs * 1.5).min(self.max_window_secs),
+                KeyCode::Char('-') => self.window_secs = (self.window_secs / 1.5).max(10.0),
+                _ => {}
+            }
+        }
+    }
+
+    fn group_of(&self, r: Row) -> usize { match r { Row::Group(g) | Row::Temp(g, _) | Row::Freq(g, _) => g } }
+
+    fn toggle(&mut self, sel: usize) {
+        match self.rows.get(sel) {
+            Some(&Row::Group(g)) => self.groups[g].visible = !self.groups[g].visible,
+            Some(&Row::Temp(g, i)) => { let it = &mut self.groups[g].items[i]; it.visible = !it.visible; }
+            Some(&Row::Freq(g, i)) => { let it = &mut self.groups[g].freqs[i]; it.visible = !it.visible; }
+            None => {}
+        }
+    }
+
+    fn draw(&mut self, f: &mut Frame) {
+        let history = self.sampler.history();
+        let h = history.lock().unwrap_or_else(|e| e.into_inner());
+        let (xmin, xmax) = if h.now > self.window_secs { (h.now - self.window_secs, h.now) } else { (0.0, self.window_secs) };
+
+        let [main, side] = Layout::horizontal([Constraint::Min(40), Constraint::Length(36)]).areas(f.area());
+        let [top, util_area, temp_area, freq_area, help] = Layout::vertical([Constraint::Length(1), Constraint::Percentage(28), Constraint::Percentage(40), Constraint::Percentage(32), Constraint::Length(1)]).areas(main);
+
+        let lod = |a: Rect| Lod { x_max: xmax, px: a.width as usize * 2 };
+        let last = |id: &str| self.channels.iter().position(|c| c.id == id).and_then(|i| h.series(i).last_y());
+        let mut rec = match (&h.recording, &h.record_error) {
+            (Some((p, rows)), _) => format!("   REC {} ({} rows)", p.display(), rows),
+            (None, Some(e)) => format!("   Recording stopped: {}", e),
+            _ => String::new(),
+        };
+        if let Some(e) = &h.push_error { rec.push_str(&format!("   Push failed: {}", e)); }
+        f.render_widget(Paragraph::new(format!("SIA - System Information Analyzer   Samples: {}   CPU: {:.0}%   RAM: {:.0}%   Window: {:.0} s{}",
+            h.samples, last("cpu/util").unwrap_or(0.0), last("ram/util").unwrap_or(0.0), self.window_secs, rec)), top);
+        f.render_widget(Paragraph::new("↑/↓ select  space toggle  t thresholds  +/- window  q quit").style(Style::default().fg(Color::DarkGray)), help);
+
+        // utilization
+        let lines: Vec<Trace> = self.util.iter().map(|&i| (color(self.channels[i].color), segments(&h, i, xmin, 1.0, lod(util_area)))).collect();
+        self.chart(f, util_area, "Utilization (%)", &h, &lines, (xmin, xmax), (0.0, 100.0), &[], |v| format!("{:.0}", v));
+
+        // temperatures, with the same dynamic range as the GUI
+        let mut lines = vec![];
+        let (mut mn, mut mx) = (f64::INFINITY, f64::NEG_INFINITY);
+        let mut thresholds = vec![];
+        for g in self.groups.iter().filter(|g| g.visible) {
+            for it in g.items.iter().filter(|it| it.visible) {
+                if let Some((a, b)) = h.series(it.idx).min_max_y(xmin, xmax) { mn = mn.min(a); mx = mx.max(b); }
+                lines.push((color(it.color), segments(&h, it.idx, xmin, 1.0, lod(temp_area))));
+            }
+            if g.show_thresholds { mx = mx.max(g.hot); thresholds.push((color(g.color), g.warn)); thresholds.push((color(g.color), g.hot)); }
+        }
+        if !mn.is_finite() || !mx.is_finite() || (mx - mn).abs() < 1e-6 { mn = 0.0; mx = 120.0; }
+        let pad = ((mx - mn) * 0.1).max(2.0);
+        self.chart(f, temp_area, "Temperatures (°C)", &h, &lines, (xmin, xmax), ((mn - pad).max(0.0), (mx + pad).min(130.0)), &thresholds, |v| format!("{:.0}", v));
+
+        // frequencies in GHz
+        let mut lines = vec![];
+        let (mut mn, mut mx) = (f64::INFINITY, f64::NEG_INFINITY);
+        for g in &self.groups { for it in g.freqs.iter().filter(|it| it.visible) {
+            if let Some((a, b)) = h.series(it.idx).min_max_y(xmin, xmax) { mn = mn.min(a / 1000.0); mx = mx.max(b / 1000.0); }
+            lines.push((color(it.color), segments(&h, it.idx, xmin, 1000.0, lod(freq_area))));
+        }}
+        if !mn.is_finite() || !mx.is_finite() || (mx - mn).abs() < 1e-6 { mn = 0.1; mx = 10.0; }
+        let pad = ((mx - mn) * 0.08).max(0.05);
+        self.chart(f, freq_area, "Frequencies (GHz)", &h, &lines, (xmin, xmax), ((mn - pad).max(0.0), (mx + pad).min(12.0)), &[], |v| format!("{:.2}", v));
+
+        // sensor list
+        let items: Vec<ListItem> = self.rows.iter().map(|r| {
+            let (text, c, on) = match *r {
+                Row::Group(g) => { let g = &self.groups[g]; return ListItem::new(Line::from(Span::styled(format!("{} {}", if g.visible { "▾" } else { "▸" }, g.display), Style::default().add_modifier(Modifier::BOLD)))); }
+                Row::Temp(g, i) => { let it = &self.groups[g].items[i]; (format!("{} {}", it.name, h.series(it.idx).last_y().map(|v| format!("{:.1}°", v)).unwrap_or_default()), it.color, it.visible) }
+                Row::Freq(g, i) => { let it = &self.groups[g].freqs[i]; (format!("{} {}", it.name, h.series(it.idx).last_y().map(|v| format!("{:.0} MHz", v)).unwrap_or_default()), it.color, it.visible) }
+            };
+            ListItem::new(Line::from(vec![Span::raw(if on { "  [x] " } else { "  [ ] " }), Span::styled("● ", Style::default().fg(color(c))), Span::raw(text)]))
+        }).collect();
+        let list = List::new(items).block(Block::bordered().title("Sensors")).highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, side, &mut self.list);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn chart(&self, f: &mut Frame, area: Rect, title: &str, h: &History, lines: &[Trace], x: (f64, f64), y: (f64, f64), hlines: &[(Color, f64)], fmt: impl Fn(f64) -> String) {
+        let hline_pts: Vec<[(f64, f64); 2]> = hlines.iter().map(|(_, v)| [(x.0, *v), (x.1, *v)]).collect();
+        // a vertical line where the machine resumed from suspend, named in the legend like the GUI's label
+        let suspends: Vec<(String, [(f64, f64); 2])> = h.suspends.iter().filter(|s| s.x >= x.0)
+            .map(|s| (format!("⏸ suspended for {}", crate::span(s.secs)), [(s.x, y.0), (s.x, y.1)])).collect();
+        let mut datasets = vec![];
+        for (c, segs) in lines { for seg in segs {
+            datasets.push(Dataset::default().marker(Marker::Braille).graph_type(GraphType::Line).style(Style::default().fg(*c)).data(seg));
+        }}
+        for ((c, _), pts) in hlines.iter().zip(&hline_pts) {
+            datasets.push(Dataset::default().marker(Marker::Dot).graph_type(GraphType::Line).style(Style::default().fg(*c).add_modifier(Modifier::DIM)).data(pts));
+        }
+        for (name, pts) in &suspends {
+            datasets.push(Dataset::default().name(name.as_str()).marker(Marker::Dot).graph_type(GraphType::Line).style(Style::default().fg(Color::DarkGray)).data(pts));
+        }
+        let clock = |x: f64| chrono::DateTime::<chrono::Local>::from(h.wall_at(x)).format("%H:%M:%S").to_string();
+        let chart = Chart::new(datasets)
+            .block(Block::bordered().title(title.to_string()))
+            .x_axis(Axis::default().bounds([x.0, x.1]).labels(vec![clock(x.0), clock((x.0 + x.1) / 2.0), clock(x.1)]).style(Style::default().fg(Color::Gray)))
+            .y_axis(Axis::default().bounds([y.0, y.1]).labels(vec![fmt(y.0), fmt((y.0 + y.1) / 2.0), fmt(y.1)]).style(Style::default().fg(Color::Gray)));
+        f.render_widget(chart, area);
+    }
+}
+
+/// Decimated to `lod`; a braille cell is two dots wide.
+fn segments(h: &History, idx: usize, x_min: f64, div: f64, lod: Lod) -> Vec<Vec<(f64, f64)>> {
+    h.series(idx).segments_lod(x_min, div, h.max_gap(), lod).iter().map(|s| s.iter().map(|[x, y]| (*x, *y)).collect()).collect()
+}